@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use psfe::font::Font;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(font) = Font::from_file(data) {
+        let file = font.construct_font_file();
+        Font::from_file(&file).expect("failed to parse saved font");
+    }
+});
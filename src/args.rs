@@ -0,0 +1,400 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use psfe::font::{BdfMapping, SourceOptions};
+
+use super::blank::BlankFont;
+use super::sheet::{ImportOptions, SheetLabels, SheetOptions};
+
+const DEFAULT_WINDOW_WIDTH: u32 = 1024;
+const DEFAULT_WINDOW_HEIGHT: u32 = 768;
+
+pub const USAGE: &str = "\
+usage: psfe [options] FONT
+       psfe info FONT
+       psfe convert [options] FONT OUTPUT
+       psfe render [--scale N] [--fg RRGGBB] [--bg RRGGBB] FONT TEXT OUTPUT
+
+commands:
+  info                 print the file's format and header, size and unicode coverage
+  convert              save FONT as OUTPUT, picking the format from its extension
+  render               draw TEXT with the font into the PNG at OUTPUT
+
+options:
+  -o, --output PATH    save to PATH instead of FONT
+  -b, --backup         keep the previous file as PATH.bak when saving
+  -s, --size WxH       window size (default 1024x768)
+  -n, --new            create a new blank font instead of opening FONT
+  -h, --help           print this help
+
+new fonts:
+  --glyph-size WxH     glyph size (default 8x16)
+  --glyphs N           glyph count (default 256), PSF1 only has 256 or 512
+  --unicode            start with an empty unicode table
+
+sheet export:
+  --sheet PATH         render every glyph into a PNG at PATH and exit
+  --columns N          glyphs per row (default 16)
+  --scale N            pixel scale factor (default 1)
+  --spacing N          pixels between glyphs (default 2)
+  --fg RRGGBB          glyph color (default ffffff)
+  --bg RRGGBB          background color (default 000000)
+  --labels KIND        label glyphs with their index or codepoint (default none)
+
+sheet import:
+  --import-sheet PATH  replace glyphs with the cells of the PNG at PATH, in order
+  --columns N          cells per row (default 16)
+  --cell WxH           cell size (default the glyph size)
+  --origin X,Y         top-left corner of the first cell (default 0,0)
+  --padding N          pixels between cells (default 0)
+  --threshold N        minimum luminance of a set pixel, 0-255 (default 128)
+  --invert             dark pixels are set instead of light ones
+
+BDF import:
+  --bdf-mapping KIND   read ENCODING as glyph slots or unicode codepoints (default
+                       unicode for ISO10646 fonts, slots otherwise)
+
+C and Rust export:
+  --no-unicode-lookup  leave the unicode lookup table out of .h and .rs files
+
+FONT may be a PSF1, PSF2, BDF, GNU Unifont .hex, psfe text or Linux kernel
+lib/fonts/font_*.c file, optionally gzipped. The output format follows the extension
+of PATH: .bdf writes BDF, .hex Unifont hex, .txt psfe text, .h a C header, .rs Rust
+source, .c a kernel font_desc source, .psf, .psfu or no extension PSF, and a trailing
+.gz compresses the result.";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Edit,
+    Info,
+    Convert,
+    Render(String),
+}
+
+pub struct Args {
+    pub command: Command,
+    pub font_path: Option<PathBuf>,
+    pub output_path: Option<PathBuf>,
+    pub backup: bool,
+    pub window_width: u32,
+    pub window_height: u32,
+    pub new_font: bool,
+    pub blank: BlankFont,
+    pub help: bool,
+    pub sheet_path: Option<PathBuf>,
+    pub sheet: SheetOptions,
+    pub import_sheet_path: Option<PathBuf>,
+    pub import_sheet: ImportOptions,
+    pub bdf_mapping: Option<BdfMapping>,
+    pub source: SourceOptions,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut parsed = Self {
+            command: Command::Edit,
+            font_path: None,
+            output_path: None,
+            backup: false,
+            window_width: DEFAULT_WINDOW_WIDTH,
+            window_height: DEFAULT_WINDOW_HEIGHT,
+            new_font: false,
+            blank: BlankFont::default(),
+            help: false,
+            sheet_path: None,
+            sheet: SheetOptions::default(),
+            import_sheet_path: None,
+            import_sheet: ImportOptions::default(),
+            bdf_mapping: None,
+            source: SourceOptions::default(),
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    let path = args.next().ok_or("missing path after --output")?;
+                    parsed.output_path = Some(path.into());
+                }
+                "-s" | "--size" => {
+                    let size = args.next().ok_or("missing size after --size")?;
+                    let (w, h) =
+                        parse_size(&size).ok_or(format!("invalid window size \"{size}\""))?;
+                    parsed.window_width = w;
+                    parsed.window_height = h;
+                }
+                "-b" | "--backup" => parsed.backup = true,
+                "-n" | "--new" => parsed.new_font = true,
+                "-h" | "--help" => parsed.help = true,
+                "--glyph-size" => {
+                    let size = args.next().ok_or("missing size after --glyph-size")?;
+                    let (w, h) = parse_size(&size)
+                        .and_then(|(w, h)| Some((u8::try_from(w).ok()?, u8::try_from(h).ok()?)))
+                        .ok_or(format!("invalid glyph size \"{size}\""))?;
+                    parsed.blank.width = w;
+                    parsed.blank.height = h;
+                }
+                "--glyphs" => parsed.blank.glyphs = parse_positive(&mut args, &arg)? as usize,
+                "--unicode" => parsed.blank.unicode = true,
+                "--sheet" => {
+                    let path = args.next().ok_or("missing path after --sheet")?;
+                    parsed.sheet_path = Some(path.into());
+                }
+                "--columns" => {
+                    let columns = parse_positive(&mut args, &arg)? as usize;
+                    parsed.sheet.columns = columns;
+                    parsed.import_sheet.columns = columns;
+                }
+                "--scale" => parsed.sheet.scale = parse_positive(&mut args, &arg)?,
+                "--spacing" => parsed.sheet.spacing = parse_value(&mut args, &arg)?,
+                "--fg" => parsed.sheet.foreground = parse_color(&mut args, &arg)?,
+                "--bg" => parsed.sheet.background = parse_color(&mut args, &arg)?,
+                "--labels" => {
+                    parsed.sheet.labels = match args.next().as_deref() {
+                        Some("none") => SheetLabels::None,
+                        Some("index") => SheetLabels::Index,
+                        Some("codepoint") => SheetLabels::Codepoint,
+                        _ => return Err("--labels takes none, index or codepoint".to_string()),
+                    }
+                }
+                "--import-sheet" => {
+                    let path = args.next().ok_or("missing path after --import-sheet")?;
+                    parsed.import_sheet_path = Some(path.into());
+                }
+                "--cell" => {
+                    let size = args.next().ok_or("missing size after --cell")?;
+                    let size = parse_size(&size).ok_or(format!("invalid cell size \"{size}\""))?;
+                    parsed.import_sheet.cell_size = Some(size);
+                }
+                "--origin" => {
+                    let origin = args.next().ok_or("missing position after --origin")?;
+                    parsed.import_sheet.origin =
+                        parse_point(&origin).ok_or(format!("invalid origin \"{origin}\""))?;
+                }
+                "--padding" => parsed.import_sheet.padding = parse_value(&mut args, &arg)?,
+                "--threshold" => parsed.import_sheet.threshold = parse_value(&mut args, &arg)?,
+                "--invert" => parsed.import_sheet.invert = true,
+                "--no-unicode-lookup" => parsed.source.unicode_lookup = false,
+                "--bdf-mapping" => {
+                    parsed.bdf_mapping = match args.next().as_deref() {
+                        Some("slots") => Some(BdfMapping::Slots),
+                        Some("unicode") => Some(BdfMapping::Unicode),
+                        _ => return Err("--bdf-mapping takes slots or unicode".to_string()),
+                    }
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option \"{arg}\"")),
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter().peekable();
+
+        parsed.command = match positional.peek().map(String::as_str) {
+            Some("info") => Command::Info,
+            Some("convert") => Command::Convert,
+            Some("render") => Command::Render(String::new()),
+            _ => Command::Edit,
+        };
+
+        if parsed.command != Command::Edit {
+            positional.next();
+        }
+
+        // New fonts aren't read from anywhere, but the editor saves them to FONT
+        if !parsed.new_font || parsed.command == Command::Edit {
+            parsed.font_path = positional.next().map(PathBuf::from);
+        }
+
+        match &mut parsed.command {
+            Command::Edit | Command::Info => (),
+            Command::Convert => {
+                let path = positional.next().ok_or("missing OUTPUT path")?;
+                parsed.output_path = Some(path.into());
+            }
+            Command::Render(text) => {
+                *text = positional.next().ok_or("missing TEXT to render")?;
+                let path = positional.next().ok_or("missing OUTPUT path")?;
+                parsed.output_path = Some(path.into());
+            }
+        }
+
+        if let Some(arg) = positional.next() {
+            return Err(format!("unexpected argument \"{arg}\""));
+        }
+
+        if parsed.font_path.is_none() && !parsed.new_font && !parsed.help {
+            return Err("no font file given".to_string());
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (w, h) = size.split_once('x')?;
+    let w = w.parse().ok().filter(|&w| w > 0)?;
+    let h = h.parse().ok().filter(|&h| h > 0)?;
+
+    Some((w, h))
+}
+
+fn parse_point(point: &str) -> Option<(u32, u32)> {
+    let (x, y) = point.split_once(',')?;
+
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+fn parse_value<T: FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    option: &str,
+) -> Result<T, String> {
+    let value = args.next().ok_or(format!("missing value after {option}"))?;
+
+    value.parse().map_err(|_| format!("invalid value \"{value}\" for {option}"))
+}
+
+fn parse_positive(args: &mut impl Iterator<Item = String>, option: &str) -> Result<u32, String> {
+    match parse_value(args, option)? {
+        0 => Err(format!("{option} must be positive")),
+        value => Ok(value),
+    }
+}
+
+fn parse_color(args: &mut impl Iterator<Item = String>, option: &str) -> Result<u32, String> {
+    let value = args.next().ok_or(format!("missing color after {option}"))?;
+    let hex = value.trim_start_matches('#');
+
+    match u32::from_str_radix(hex, 16) {
+        Ok(color) if hex.len() == 6 => Ok(color),
+        _ => Err(format!("invalid color \"{value}\", expected RRGGBB")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse_from(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn font_and_options() {
+        let args = parse(&["-s", "800x600", "font.psf", "--output", "out.psf"]).unwrap();
+
+        assert_eq!(args.font_path, Some("font.psf".into()));
+        assert_eq!(args.output_path, Some("out.psf".into()));
+        assert_eq!((args.window_width, args.window_height), (800, 600));
+        assert!(!args.new_font);
+    }
+
+    #[test]
+    fn new_font_without_path() {
+        let args = parse(&["--new"]).unwrap();
+
+        assert!(args.new_font);
+        assert_eq!(args.font_path, None);
+        assert_eq!(args.blank, BlankFont::default());
+
+        let args = parse(&[
+            "-n",
+            "--glyph-size",
+            "12x24",
+            "--glyphs",
+            "1000",
+            "--unicode",
+        ]);
+        let blank = args.unwrap().blank;
+
+        assert_eq!((blank.width, blank.height, blank.glyphs), (12, 24, 1000));
+        assert!(blank.unicode);
+        assert!(parse(&["-n", "--glyph-size", "256x16"]).is_err());
+    }
+
+    #[test]
+    fn sheet_options() {
+        let args = parse(&[
+            "a.psf", "--sheet", "a.png", "--scale", "3", "--fg", "#ff8000",
+        ]);
+        let args = args.unwrap();
+
+        assert_eq!(args.sheet_path, Some("a.png".into()));
+        assert_eq!(args.sheet.scale, 3);
+        assert_eq!(args.sheet.foreground, 0xff8000);
+        assert_eq!(args.sheet.columns, 16);
+
+        assert!(parse(&["a.psf", "--columns", "0"]).is_err());
+        assert!(parse(&["a.psf", "--bg", "fff"]).is_err());
+        assert!(parse(&["a.psf", "--labels", "names"]).is_err());
+    }
+
+    #[test]
+    fn import_sheet_options() {
+        let args =
+            "a.psf --import-sheet a.png --cell 10x18 --origin 2,4 --columns 8 --threshold 40";
+        let args = parse(&args.split(' ').collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(args.import_sheet_path, Some("a.png".into()));
+        assert_eq!(args.import_sheet.cell_size, Some((10, 18)));
+        assert_eq!(args.import_sheet.origin, (2, 4));
+        assert_eq!(args.import_sheet.columns, 8);
+        assert_eq!(args.import_sheet.threshold, 40);
+
+        assert!(parse(&["a.psf", "--threshold", "256"]).is_err());
+        assert!(parse(&["a.psf", "--origin", "2"]).is_err());
+    }
+
+    #[test]
+    fn bdf_mapping() {
+        assert_eq!(parse(&["a.bdf"]).unwrap().bdf_mapping, None);
+
+        let args = parse(&["convert", "--bdf-mapping", "slots", "a.bdf", "a.psf"]).unwrap();
+        assert_eq!(args.bdf_mapping, Some(BdfMapping::Slots));
+
+        assert!(parse(&["a.bdf", "--bdf-mapping", "latin1"]).is_err());
+    }
+
+    #[test]
+    fn source_options() {
+        assert!(parse(&["a.psf"]).unwrap().source.unicode_lookup);
+
+        let args = parse(&["convert", "a.psf", "a.h", "--no-unicode-lookup"]).unwrap();
+        assert!(!args.source.unicode_lookup);
+    }
+
+    #[test]
+    fn subcommands() {
+        let args = parse(&["info", "a.psf"]).unwrap();
+        assert_eq!(args.command, Command::Info);
+        assert_eq!(args.font_path, Some("a.psf".into()));
+
+        let args = parse(&["convert", "a.psf", "a.bdf"]).unwrap();
+        assert_eq!(args.command, Command::Convert);
+        assert_eq!(args.output_path, Some("a.bdf".into()));
+
+        let args = parse(&["render", "--scale", "2", "a.psf", "Hello", "hello.png"]).unwrap();
+        assert_eq!(args.command, Command::Render("Hello".to_string()));
+        assert_eq!(args.output_path, Some("hello.png".into()));
+
+        assert!(parse(&["info", "a.psf", "b.psf"]).is_err());
+        assert!(parse(&["convert", "a.psf"]).is_err());
+        assert!(parse(&["render", "a.psf", "Hello"]).is_err());
+
+        let args = parse(&["convert", "--new", "--glyphs", "512", "blank.psf"]).unwrap();
+        assert_eq!((args.font_path, args.output_path), (None, Some("blank.psf".into())));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a.psf", "b.psf"]).is_err());
+        assert!(parse(&["--size", "800", "a.psf"]).is_err());
+        assert!(parse(&["--size", "0x600", "a.psf"]).is_err());
+        assert!(parse(&["--output"]).is_err());
+        assert!(parse(&["--frobnicate", "a.psf"]).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use psfe::font::{Font, UnicodeEntry};
+
+// Dimensions of a font made from scratch, written like 8x16, 8x16x512 or 12x24x1000u where
+// the optional u adds an empty unicode table
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlankFont {
+    pub width: u8,
+    pub height: u8,
+    pub glyphs: usize,
+    pub unicode: bool,
+}
+
+impl Default for BlankFont {
+    fn default() -> Self {
+        Self {
+            width: 8,
+            height: 16,
+            glyphs: 256,
+            unicode: false,
+        }
+    }
+}
+
+impl BlankFont {
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (spec, unicode) = match spec.strip_suffix('u') {
+            Some(spec) => (spec, true),
+            None => (spec, false),
+        };
+
+        let mut parts = spec.split('x');
+        let width = parts.next()?.parse().ok().filter(|&width| width > 0)?;
+        let height = parts.next()?.parse().ok().filter(|&height| height > 0)?;
+        let glyphs = match parts.next() {
+            Some(glyphs) => glyphs.parse().ok().filter(|&glyphs| glyphs > 0)?,
+            None => 256,
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            glyphs,
+            unicode,
+        })
+    }
+
+    // PSF1 only has 8 pixel wide fonts with 256 or 512 glyphs, anything else is PSF2
+    pub fn build(&self) -> Font {
+        let mut font = Font::new(self.width, self.height, self.glyphs);
+
+        if self.unicode {
+            font.unicode_table = Some(vec![UnicodeEntry::default(); self.glyphs]);
+        }
+
+        font.version = if font.fits_psf1() { 1 } else { 2 };
+        font
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_build() {
+        let blank = BlankFont::parse("8x16x512u").unwrap();
+        let font = blank.build();
+
+        assert_eq!((font.width, font.height, font.glyphs.len()), (8, 16, 512));
+        assert_eq!((font.version, font.unicode_table.unwrap().len()), (1, 512));
+
+        let font = BlankFont::parse("8x8x300").unwrap().build();
+        assert_eq!((font.version, font.unicode_table.is_none()), (2, true));
+
+        assert_eq!(
+            BlankFont::parse("12x24"),
+            Some(BlankFont {
+                width: 12,
+                height: 24,
+                ..BlankFont::default()
+            })
+        );
+        assert_eq!(BlankFont::parse("8x0"), None);
+        assert_eq!(BlankFont::parse("8x16x0"), None);
+        assert_eq!(BlankFont::parse("300x16"), None);
+        assert_eq!(BlankFont::parse("8x16x256x1"), None);
+    }
+}
@@ -0,0 +1,196 @@
+use psfe::font::{Font, PsfHeader};
+
+use super::formats::InputFormat;
+use super::framebuffer::Framebuffer;
+use super::sheet::SheetOptions;
+
+// The format and header describe the file as it was read, new fonts have neither
+pub fn info(font: &Font, format: Option<&InputFormat>) -> String {
+    let charsize = (font.width as usize).div_ceil(8) * font.height as usize;
+    let mut lines = match format {
+        Some(format) => format_lines(format),
+        None => vec!["format: new font".to_string()],
+    };
+
+    lines.push(format!("size: {}x{}, {charsize} bytes per glyph", font.width, font.height));
+    lines.push(format!("glyphs: {}", font.glyphs.len()));
+
+    match &font.unicode_table {
+        Some(table) => {
+            let mut codepoints: Vec<u32> =
+                table.iter().flat_map(|entry| entry.codepoints.iter().copied()).collect();
+            let sequences = table.iter().map(|entry| entry.sequences.len()).sum::<usize>();
+            let unmapped = table.iter().filter(|entry| entry.codepoints.is_empty()).count();
+
+            codepoints.sort_unstable();
+            codepoints.dedup();
+
+            let count = codepoints.len();
+            lines.push(format!("unicode table: {count} codepoints, {sequences} sequences"));
+            lines.push(format!("unmapped glyphs: {unmapped}"));
+            lines.push(format!("coverage: {}", coverage_ranges(&codepoints)));
+        }
+        None => lines.push("unicode table: none".to_string()),
+    }
+
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+fn format_lines(format: &InputFormat) -> Vec<String> {
+    let name = match format {
+        InputFormat::Gzip(format) => {
+            let mut lines = format_lines(format);
+            lines[0] += ", gzip compressed";
+            return lines;
+        }
+        InputFormat::Psf(PsfHeader::Psf1 { mode, charsize }) => {
+            let header = format!("header: mode 0x{mode:02x}, charsize {charsize}");
+            return vec!["format: PSF1".to_string(), header];
+        }
+        InputFormat::Psf(PsfHeader::Psf2(header)) => {
+            let header = format!(
+                "header: version {}, headersize {}, flags 0x{:02x}, length {}, charsize {}, \
+                 height {}, width {}",
+                header.version,
+                header.headersize,
+                header.flags,
+                header.length,
+                header.charsize,
+                header.height,
+                header.width,
+            );
+            return vec!["format: PSF2".to_string(), header];
+        }
+        InputFormat::Bdf => "BDF",
+        InputFormat::Hex => "GNU Unifont hex",
+        InputFormat::Text => "psfe text",
+        InputFormat::Kernel => "Linux kernel font_desc source",
+    };
+
+    vec![format!("format: {name}")]
+}
+
+// Lines are split on '\n', characters without a glyph fall back to U+FFFD, then '?', then
+// the first glyph
+pub fn render_text(font: &Font, text: &str, options: &SheetOptions) -> Result<Framebuffer, String> {
+    let fw = font.width as u32;
+    let fh = font.height as u32;
+    let lines: Vec<&str> = text.split('\n').collect();
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+
+    let fallback = font.glyph_index(0xfffd).or_else(|| font.glyph_index('?'.into())).unwrap_or(0);
+    let width = (columns as u64 * u64::from(fw)).max(1);
+    let height = (lines.len() as u64 * u64::from(fh)).max(1);
+    let mut fb = Framebuffer::try_new(width, height)?;
+    let (width, height) = (fb.width, fb.height);
+
+    fb.draw_rect(0, 0, width, height, options.background);
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let idx = font.glyph_index(c.into()).unwrap_or(fallback);
+            let Some(glyph) = font.glyphs.get(idx) else {
+                continue;
+            };
+
+            let offset_x = column as u32 * fw;
+            let offset_y = row as u32 * fh;
+
+            for y in 0..fh {
+                for x in 0..fw {
+                    if glyph.get(x as usize, y as usize) {
+                        fb.draw_pixel(offset_x + x, offset_y + y, options.foreground);
+                    }
+                }
+            }
+        }
+    }
+
+    fb.scaled(options.scale.max(1))
+}
+
+// Consecutive codepoints are merged, like U+0020-U+007E, U+00A0
+fn coverage_ranges(codepoints: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = vec![];
+
+    for &codepoint in codepoints {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == codepoint => *end = codepoint,
+            _ => ranges.push((codepoint, codepoint)),
+        }
+    }
+
+    let ranges: Vec<String> = ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                format!("U+{start:04X}")
+            } else {
+                format!("U+{start:04X}-U+{end:04X}")
+            }
+        })
+        .collect();
+
+    ranges.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats;
+    use psfe::font::SourceOptions;
+    use std::path::Path;
+
+    #[test]
+    fn info_and_render() {
+        let mut font = Font::new(8, 8, 256);
+        font.glyphs[1].set(0, 0);
+        font.glyphs[2].set(7, 7);
+
+        for (idx, codepoints) in [
+            (0, vec![0xfffd]),
+            (1, vec![0x41, 0x42, 0x44]),
+            (2, vec![0x43]),
+        ] {
+            font.unicode_entry_mut(idx).codepoints = codepoints;
+        }
+
+        let (read, format) = formats::decode_font(&font.construct_font_file(), None).unwrap();
+        let lines = info(&read, Some(&format));
+        assert!(lines.starts_with("format: PSF1\nheader: mode 0x02, charsize 8\nsize: 8x8, 8 "));
+        assert!(lines.contains("unicode table: 5 codepoints, 0 sequences\nunmapped glyphs: 253\n"));
+        assert!(lines.ends_with("coverage: U+0041-U+0044, U+FFFD\n"));
+
+        // The header is the one in the file, not the one psfe would write
+        let mut wide = Font::new(12, 8, 3);
+        wide.psf2_charsize = 20;
+        let file = formats::encode_font(&wide, Path::new("a.psf.gz"), &SourceOptions::default());
+        let (read, format) = formats::decode_font(&file.unwrap(), None).unwrap();
+        assert!(info(&read, Some(&format)).starts_with(
+            "format: PSF2, gzip compressed\nheader: version 0, headersize 32, flags 0x00, \
+             length 3, charsize 20, height 8, width 12\nsize: 12x8, 16 bytes per glyph\n"
+        ));
+
+        let bdf = font.construct_bdf_file().into_bytes();
+        let (read, format) = formats::decode_font(&bdf, None).unwrap();
+        assert!(info(&read, Some(&format)).starts_with("format: BDF\nsize: 8x8"));
+        assert!(info(&font, None).starts_with("format: new font\n"));
+
+        let options = SheetOptions {
+            scale: 2,
+            ..SheetOptions::default()
+        };
+        let fb = render_text(&font, "AC\nZ", &options).unwrap();
+        let pixel = |x: u32, y: u32| fb.pixels[(y * fb.width + x) as usize] == 0xffffffff;
+
+        assert_eq!((fb.width, fb.height), (32, 32));
+        assert!(pixel(0, 0) && pixel(1, 1) && !pixel(2, 0));
+        assert!(pixel(31, 15) && !pixel(31, 17));
+
+        let options = SheetOptions {
+            scale: 70000,
+            ..options
+        };
+        assert!(render_text(&font, "A", &options).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+use super::{Font, LineError, UnicodeEntry};
+
+// Slot mapped fonts get a glyph for every index up to the largest ENCODING
+const MAX_SLOTS: usize = 65536;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BdfMapping {
+    // ENCODING is the glyph index
+    Slots,
+    // Glyphs are stored in file order and ENCODING goes into the unicode table
+    Unicode,
+}
+
+#[derive(Clone, Copy)]
+struct BoundingBox {
+    width: i32,
+    height: i32,
+    x: i32,
+    y: i32,
+}
+
+struct BdfGlyph {
+    line: usize,
+    encoding: Option<u32>,
+    bbx: BoundingBox,
+    rows: Vec<Vec<u8>>,
+}
+
+struct BdfFile {
+    bbx: BoundingBox,
+    bbx_line: usize,
+    end_line: usize,
+    unicode: bool,
+    glyphs: Vec<BdfGlyph>,
+}
+
+impl Font {
+    pub fn from_bdf(text: &str) -> Result<Self, LineError> {
+        let bdf = BdfFile::parse(text)?;
+        let mapping = if bdf.unicode {
+            BdfMapping::Unicode
+        } else {
+            BdfMapping::Slots
+        };
+
+        bdf.into_font(mapping)
+    }
+
+    pub fn from_bdf_with_mapping(text: &str, mapping: BdfMapping) -> Result<Self, LineError> {
+        BdfFile::parse(text)?.into_font(mapping)
+    }
+
+    // PSF has no notion of a baseline, so a quarter of the height is assumed to be below it.
+    // At 72 dpi a point is a pixel, which keeps PIXEL_SIZE, POINT_SIZE and SWIDTH consistent
+    pub fn construct_bdf_file(&self) -> String {
+        let width = u32::from(self.width);
+        let height = u32::from(self.height);
+        let descent = height / 4;
+        let ascent = height - descent;
+
+        // (encoding, glyph index) pairs, glyphs without a codepoint are written unencoded
+        let mut chars = vec![];
+
+        match &self.unicode_table {
+            Some(table) => {
+                for (idx, entry) in table.iter().enumerate().take(self.glyphs.len()) {
+                    if entry.codepoints.is_empty() {
+                        chars.push((None, idx));
+                    }
+
+                    chars.extend(entry.codepoints.iter().map(|&codepoint| (Some(codepoint), idx)));
+                }
+            }
+            None => chars.extend((0..self.glyphs.len()).map(|idx| (Some(idx as u32), idx))),
+        }
+
+        let unicode = self.unicode_table.is_some();
+        let (registry, encoding) = if unicode {
+            ("ISO10646", "1")
+        } else {
+            ("FontSpecific", "0")
+        };
+
+        let encodings = chars.iter().filter_map(|&(encoding, _)| encoding);
+        let default_char = if unicode {
+            encodings.clone().find(|&encoding| encoding == 0xfffd).or(encodings.min())
+        } else {
+            Some(0)
+        };
+
+        let mut properties = vec![
+            format!("PIXEL_SIZE {height}"),
+            format!("POINT_SIZE {}", height * 10),
+            "RESOLUTION_X 72".to_string(),
+            "RESOLUTION_Y 72".to_string(),
+            "SPACING \"C\"".to_string(),
+            format!("AVERAGE_WIDTH {}", width * 10),
+            format!("CHARSET_REGISTRY \"{registry}\""),
+            format!("CHARSET_ENCODING \"{encoding}\""),
+            format!("FONT_ASCENT {ascent}"),
+            format!("FONT_DESCENT {descent}"),
+        ];
+
+        if let Some(default_char) = default_char {
+            properties.push(format!("DEFAULT_CHAR {default_char}"));
+        }
+
+        let mut out = String::new();
+        let mut line = |text: String| {
+            out.push_str(&text);
+            out.push('\n');
+        };
+
+        line("STARTFONT 2.1".to_string());
+        line(format!(
+            "FONT -psfe-fixed-medium-r-normal--{height}-{}-72-72-c-{}-{registry}-{encoding}",
+            height * 10,
+            width * 10
+        ));
+        line(format!("SIZE {height} 72 72"));
+        line(format!("FONTBOUNDINGBOX {width} {height} 0 -{descent}"));
+        line(format!("STARTPROPERTIES {}", properties.len()));
+        properties.into_iter().for_each(&mut line);
+        line("ENDPROPERTIES".to_string());
+        line(format!("CHARS {}", chars.len()));
+
+        let bytes_per_row = (width as usize).div_ceil(8);
+
+        for (encoding, idx) in chars {
+            match encoding {
+                Some(encoding) if unicode => line(format!("STARTCHAR U+{encoding:04X}")),
+                _ => line(format!("STARTCHAR glyph{idx}")),
+            }
+
+            match encoding {
+                Some(encoding) => line(format!("ENCODING {encoding}")),
+                None => line(format!("ENCODING -1 {idx}")),
+            }
+
+            line(format!("SWIDTH {} 0", width * 1000 / height.max(1)));
+            line(format!("DWIDTH {width} 0"));
+            line(format!("BBX {width} {height} 0 -{descent}"));
+            line("BITMAP".to_string());
+
+            for row in self.glyphs[idx].serialize().chunks(bytes_per_row) {
+                line(row.iter().map(|byte| format!("{byte:02X}")).collect());
+            }
+
+            line("ENDCHAR".to_string());
+        }
+
+        line("ENDFONT".to_string());
+
+        out
+    }
+}
+
+impl BdfFile {
+    fn parse(text: &str) -> Result<Self, LineError> {
+        let mut lines = text.lines().enumerate().map(|(idx, line)| (idx + 1, line.trim()));
+        let mut bbx = None;
+        let mut bbx_line = 0;
+        let mut unicode = false;
+        let mut glyphs = vec![];
+        let mut last_line = 0;
+
+        while let Some((line, content)) = lines.next() {
+            last_line = line;
+            let (keyword, args) = content.split_once(' ').unwrap_or((content, ""));
+
+            match keyword {
+                "FONTBOUNDINGBOX" => {
+                    bbx = Some(BoundingBox::parse(args, line)?);
+                    bbx_line = line;
+                }
+                "CHARSET_REGISTRY" => {
+                    unicode = args.trim_matches('"').eq_ignore_ascii_case("ISO10646");
+                }
+                "STARTCHAR" => {
+                    let font_bbx = bbx
+                        .ok_or_else(|| LineError::new(line, "STARTCHAR before FONTBOUNDINGBOX"))?;
+                    glyphs.push(BdfGlyph::parse(&mut lines, line, font_bbx)?);
+                }
+                "ENDFONT" => {
+                    let bbx = bbx.ok_or_else(|| LineError::new(line, "missing FONTBOUNDINGBOX"))?;
+
+                    return Ok(Self {
+                        bbx,
+                        bbx_line,
+                        end_line: line,
+                        unicode,
+                        glyphs,
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        Err(LineError::new(last_line, "missing ENDFONT"))
+    }
+
+    fn into_font(self, mapping: BdfMapping) -> Result<Font, LineError> {
+        let size = |size: i32| u8::try_from(size).ok().filter(|&size| size > 0);
+
+        let (Some(width), Some(height)) = (size(self.bbx.width), size(self.bbx.height)) else {
+            let message = format!("unsupported size {}x{}", self.bbx.width, self.bbx.height);
+            return Err(LineError::new(self.bbx_line, &message));
+        };
+
+        let encoded = self.glyphs.iter().filter_map(|glyph| glyph.encoding.map(|enc| (enc, glyph)));
+
+        let mut font = match mapping {
+            BdfMapping::Slots => {
+                if let Some((encoding, glyph)) =
+                    encoded.clone().find(|&(enc, _)| enc as usize >= MAX_SLOTS)
+                {
+                    let message = format!("ENCODING {encoding} is too large for a glyph index");
+                    return Err(LineError::new(glyph.line, &message));
+                }
+
+                let max_encoding = encoded.clone().map(|(enc, _)| enc as usize).max().unwrap_or(0);
+                let num_glyphs = match max_encoding {
+                    0..=255 => 256,
+                    256..=511 => 512,
+                    _ => max_encoding + 1,
+                };
+
+                let mut font = Font::new(width, height, num_glyphs);
+
+                for (encoding, glyph) in encoded {
+                    glyph.draw(&mut font, encoding as usize, self.bbx);
+                }
+
+                font
+            }
+            BdfMapping::Unicode => {
+                let encoded: Vec<_> = encoded.collect();
+
+                if let Some((encoding, glyph)) = encoded.iter().find(|&&(enc, _)| enc > 0x10ffff) {
+                    let message = format!("ENCODING {encoding} is not a unicode codepoint");
+                    return Err(LineError::new(glyph.line, &message));
+                }
+
+                if encoded.is_empty() {
+                    return Err(LineError::new(self.end_line, "no glyphs with an ENCODING"));
+                }
+
+                let mut font = Font::new(width, height, encoded.len());
+                let mut table = vec![];
+
+                for (idx, (encoding, glyph)) in encoded.into_iter().enumerate() {
+                    glyph.draw(&mut font, idx, self.bbx);
+                    table.push(UnicodeEntry {
+                        codepoints: vec![encoding],
+                        sequences: vec![],
+                    });
+                }
+
+                font.unicode_table = Some(table);
+                font
+            }
+        };
+
+        font.version = if font.fits_psf1() { 1 } else { 2 };
+
+        Ok(font)
+    }
+}
+
+impl BdfGlyph {
+    fn parse<'a>(
+        lines: &mut impl Iterator<Item = (usize, &'a str)>,
+        start_line: usize,
+        font_bbx: BoundingBox,
+    ) -> Result<Self, LineError> {
+        let mut encoding = None;
+        let mut bbx = font_bbx;
+        let mut last_line = 0;
+
+        while let Some((line, content)) = lines.next() {
+            last_line = line;
+            let (keyword, args) = content.split_once(' ').unwrap_or((content, ""));
+
+            match keyword {
+                "ENCODING" => {
+                    let value = args.split_whitespace().next().unwrap_or_default();
+                    let value: i64 =
+                        value.parse().map_err(|_| LineError::new(line, "bad ENCODING"))?;
+                    encoding = u32::try_from(value).ok();
+                }
+                "BBX" => bbx = BoundingBox::parse(args, line)?,
+                "BITMAP" => {
+                    let mut rows = vec![];
+
+                    for (line, content) in lines.by_ref() {
+                        last_line = line;
+
+                        if content == "ENDCHAR" {
+                            return Ok(Self {
+                                line: start_line,
+                                encoding,
+                                bbx,
+                                rows,
+                            });
+                        }
+
+                        rows.push(
+                            parse_hex_row(content)
+                                .ok_or_else(|| LineError::new(line, "bad bitmap row"))?,
+                        );
+                    }
+
+                    break;
+                }
+                "ENDCHAR" => {
+                    return Ok(Self {
+                        line: start_line,
+                        encoding,
+                        bbx,
+                        rows: vec![],
+                    })
+                }
+                _ => (),
+            }
+        }
+
+        Err(LineError::new(last_line, "unexpected end of file inside a glyph"))
+    }
+
+    // The bitmap is positioned relative to the baseline, the cell's bottom-left corner
+    // sits at the font bounding box offset
+    fn draw(&self, font: &mut Font, idx: usize, font_bbx: BoundingBox) {
+        let origin_x = self.bbx.x - font_bbx.x;
+        let origin_y = (font_bbx.height + font_bbx.y) - (self.bbx.height + self.bbx.y);
+        let glyph = &mut font.glyphs[idx];
+
+        for (row_idx, row) in self.rows.iter().enumerate().take(self.bbx.height as usize) {
+            for x in 0..self.bbx.width {
+                let byte = row.get(x as usize / 8).copied().unwrap_or(0);
+
+                if byte & (0x80 >> (x % 8)) == 0 {
+                    continue;
+                }
+
+                let cell_x = origin_x + x;
+                let cell_y = origin_y + row_idx as i32;
+
+                if (0..font_bbx.width).contains(&cell_x) && (0..font_bbx.height).contains(&cell_y) {
+                    glyph.set(cell_x as usize, cell_y as usize);
+                }
+            }
+        }
+    }
+}
+
+impl BoundingBox {
+    fn parse(args: &str, line: usize) -> Result<Self, LineError> {
+        let values: Vec<i32> = args
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| LineError::new(line, "bad bounding box"))?;
+
+        let [width, height, x, y] = values[..] else {
+            return Err(LineError::new(line, "bounding box needs 4 values"));
+        };
+
+        if width < 0 || height < 0 {
+            return Err(LineError::new(line, "negative bounding box size"));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            x,
+            y,
+        })
+    }
+}
+
+fn parse_hex_row(row: &str) -> Option<Vec<u8>> {
+    if !row.len().is_multiple_of(2) || !row.is_ascii() {
+        return None;
+    }
+
+    (0..row.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&row[idx..idx + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BDF: &str = "\
+STARTFONT 2.1
+FONT -test-fixed-medium-r-normal--16-160-75-75-c-80-iso10646-1
+SIZE 16 75 75
+FONTBOUNDINGBOX 8 16 0 -4
+STARTPROPERTIES 2
+CHARSET_REGISTRY \"ISO10646\"
+CHARSET_ENCODING \"1\"
+ENDPROPERTIES
+CHARS 3
+STARTCHAR A
+ENCODING 65
+SWIDTH 500 0
+DWIDTH 8 0
+BBX 5 3 1 0
+BITMAP
+20
+50
+F8
+ENDCHAR
+STARTCHAR g
+ENCODING 103
+BBX 8 2 0 -2
+BITMAP
+FF
+81
+ENDCHAR
+STARTCHAR unencoded
+ENCODING -1 300
+BBX 8 1 0 0
+BITMAP
+FF
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn unicode_mapping() {
+        let font = Font::from_bdf(BDF).unwrap();
+        let table = font.unicode_table.as_ref().unwrap();
+
+        assert_eq!((font.width, font.height, font.glyphs.len()), (8, 16, 2));
+        assert_eq!(table[0].codepoints, [65]);
+        assert_eq!(table[1].codepoints, [103]);
+
+        // Baseline is 4 pixels above the bottom of the cell
+        let a = &font.glyphs[0];
+        assert!(a.get(3, 9) && a.get(2, 10) && a.get(4, 10));
+        assert!((1..6).all(|x| a.get(x, 11)));
+        assert!(!a.get(0, 11) && !a.get(3, 12));
+
+        let g = &font.glyphs[1];
+        assert!((0..8).all(|x| g.get(x, 12)));
+        assert!(g.get(0, 13) && g.get(7, 13) && !g.get(1, 13));
+    }
+
+    #[test]
+    fn slot_mapping() {
+        let font = Font::from_bdf_with_mapping(BDF, BdfMapping::Slots).unwrap();
+
+        assert_eq!(font.glyphs.len(), 256);
+        assert!(font.unicode_table.is_none());
+        assert!(font.glyphs[65].get(3, 9));
+        assert!(font.glyphs[103].get(0, 12));
+        assert_eq!(font.version, 1);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Font::from_bdf("STARTFONT 2.1\nSTARTCHAR A\n").err().unwrap();
+        assert_eq!(err.line, 2);
+
+        let bad_row = BDF.replace("F8", "F8X");
+        assert_eq!(Font::from_bdf(&bad_row).err().unwrap().line, 18);
+
+        let truncated = BDF.replace("ENDFONT\n", "");
+        assert!(Font::from_bdf(&truncated).is_err());
+
+        let no_width = BDF.replace("FONTBOUNDINGBOX 8", "FONTBOUNDINGBOX 0");
+        assert_eq!(Font::from_bdf(&no_width).err().unwrap().line, 4);
+
+        let not_unicode = BDF.replace("ENCODING 103", "ENCODING 2000000");
+        assert_eq!(Font::from_bdf(&not_unicode).err().unwrap().line, 20);
+        assert!(Font::from_bdf_with_mapping(&not_unicode, BdfMapping::Slots).is_err());
+
+        let unencoded =
+            BDF.replace("ENCODING 65", "ENCODING -1").replace("ENCODING 103", "ENCODING -1");
+        assert_eq!(Font::from_bdf(&unencoded).err().unwrap().line, 33);
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let font = Font::from_bdf(BDF).unwrap();
+        let bdf = font.construct_bdf_file();
+
+        assert!(bdf.starts_with("STARTFONT 2.1\n"));
+        assert!(bdf.contains("\nSIZE 16 72 72\n"));
+        assert!(bdf.contains("PIXEL_SIZE 16\nPOINT_SIZE 160\nRESOLUTION_X 72\n"));
+        assert!(bdf.contains("FONTBOUNDINGBOX 8 16 0 -4\n"));
+        assert!(bdf.contains("FONT_ASCENT 12\nFONT_DESCENT 4\nDEFAULT_CHAR 65\n"));
+        assert!(bdf.contains("STARTCHAR U+0041\nENCODING 65\nSWIDTH 500 0\nDWIDTH 8 0\n"));
+
+        let parsed = Font::from_bdf(&bdf).unwrap();
+        assert!(parsed.glyphs == font.glyphs);
+        assert_eq!(parsed.unicode_table.unwrap()[1].codepoints, [103]);
+
+        let slots = Font::from_bdf_with_mapping(BDF, BdfMapping::Slots).unwrap();
+        let parsed = Font::from_bdf(&slots.construct_bdf_file()).unwrap();
+        assert!(parsed.unicode_table.is_none());
+        assert!(parsed.glyphs == slots.glyphs);
+    }
+}
@@ -0,0 +1,162 @@
+use super::{BitMatrix, Font, LineError, UnicodeEntry};
+
+const HEX_HEIGHT: u8 = 16;
+
+impl Font {
+    // Glyphs are stored in file order and every line's codepoint goes into the unicode
+    // table. Halfwidth glyphs in a file that also has fullwidth ones use the left half and
+    // keep their width in glyph_widths
+    pub fn from_hex(text: &str) -> Result<Self, LineError> {
+        let mut glyphs = vec![];
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: &str| LineError::new(idx + 1, message);
+
+            let (codepoint, bitmap) = line.split_once(':').ok_or_else(|| error("missing ':'"))?;
+            let codepoint = u32::from_str_radix(codepoint, 16)
+                .ok()
+                .filter(|&codepoint| codepoint <= 0x10ffff)
+                .ok_or_else(|| error("bad codepoint"))?;
+            let width = match bitmap.len() {
+                32 => 8,
+                64 => 16,
+                _ => return Err(error("bitmap must be 32 or 64 hex digits")),
+            };
+            let rows = (0..bitmap.len())
+                .step_by(2)
+                .map(|idx| bitmap.get(idx..idx + 2).and_then(|d| u8::from_str_radix(d, 16).ok()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| error("bad bitmap"))?;
+
+            glyphs.push((codepoint, BitMatrix::deserialize(width, HEX_HEIGHT, &rows)));
+        }
+
+        let width = glyphs.iter().map(|(_, glyph)| glyph.width).max().unwrap_or(8);
+        let mut font = Font::new(width, HEX_HEIGHT, glyphs.len());
+        let mut table = vec![];
+
+        for (idx, (codepoint, glyph)) in glyphs.into_iter().enumerate() {
+            if glyph.width < width {
+                font.glyph_widths.insert(idx, glyph.width);
+            }
+
+            for y in 0..HEX_HEIGHT as usize {
+                for x in 0..glyph.width as usize {
+                    font.glyphs[idx].set_to(x, y, glyph.get(x, y));
+                }
+            }
+
+            table.push(UnicodeEntry {
+                codepoints: vec![codepoint],
+                sequences: vec![],
+            });
+        }
+
+        font.unicode_table = Some(table);
+        font.version = if font.fits_psf1() { 1 } else { 2 };
+
+        Ok(font)
+    }
+
+    pub fn fits_hex(&self) -> bool {
+        self.height == HEX_HEIGHT && self.width <= 16
+    }
+
+    // Needs fits_hex(). Glyphs are padded to 8 or 16 pixels, lines are sorted by codepoint
+    // and glyphs without one are left out. Without a unicode table the glyph index is the
+    // codepoint
+    pub fn construct_hex_file(&self) -> String {
+        assert!(self.fits_hex());
+
+        let mut lines: Vec<(u32, usize)> = match &self.unicode_table {
+            Some(table) => table
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, entry)| entry.codepoints.iter().map(move |&cp| (cp, idx)))
+                .collect(),
+            None => (0..self.glyphs.len()).map(|idx| (idx as u32, idx)).collect(),
+        };
+
+        lines.sort_by_key(|&(codepoint, _)| codepoint);
+        lines.dedup_by_key(|&mut (codepoint, _)| codepoint);
+
+        let mut out = String::new();
+
+        for (codepoint, idx) in lines {
+            let glyph = &self.glyphs[idx];
+            let width = if self.hex_width(idx) <= 8 { 8 } else { 16 };
+            let mut padded = BitMatrix::new(width, HEX_HEIGHT);
+
+            for y in 0..HEX_HEIGHT as usize {
+                for x in 0..glyph.width.min(width) as usize {
+                    padded.set_to(x, y, glyph.get(x, y));
+                }
+            }
+
+            let bitmap: String = padded.serialize().iter().map(|b| format!("{b:02X}")).collect();
+            out.push_str(&format!("{codepoint:04X}:{bitmap}\n"));
+        }
+
+        out
+    }
+
+    // A halfwidth glyph stays halfwidth unless pixels were drawn past its width
+    fn hex_width(&self, idx: usize) -> u8 {
+        let glyph = &self.glyphs[idx];
+        let narrow = self.glyph_widths.get(&idx).filter(|&&width| {
+            (width..self.width).all(|x| (0..self.height).all(|y| !glyph.get(x.into(), y.into())))
+        });
+
+        narrow.copied().unwrap_or(self.width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = "\
+0041:0000000018242442427E424242420000
+4E00:00000000000000000000FFFE0000000000000000000000000000000000000000
+";
+
+    #[test]
+    fn mixed_widths() {
+        let font = Font::from_hex(HEX).unwrap();
+        let table = font.unicode_table.as_ref().unwrap();
+
+        assert_eq!((font.width, font.height, font.glyphs.len()), (16, 16, 2));
+        assert_eq!(table[1].codepoints, [0x4e00]);
+        assert!(font.glyphs[0].get(3, 4) && !font.glyphs[0].get(11, 4));
+        assert!((0..15).all(|x| font.glyphs[1].get(x, 5)) && !font.glyphs[1].get(15, 5));
+
+        // Halfwidth glyphs keep their width until something is drawn past it
+        assert_eq!(font.construct_hex_file(), HEX);
+
+        let mut font = font;
+        font.glyphs[0].set(12, 0);
+        assert!(font.construct_hex_file().starts_with("0041:0008000000000000180024"));
+    }
+
+    #[test]
+    fn export_halfwidth() {
+        let font = Font::from_hex(&HEX[..38]).unwrap();
+        assert_eq!(font.width, 8);
+        assert_eq!(font.construct_hex_file(), HEX[..38]);
+
+        let mut font = Font::new(8, 16, 256);
+        font.glyphs[0x41].set(0, 0);
+        assert!(font.construct_hex_file().contains("\n0041:80000000000000000000000000000000\n"));
+
+        assert!(!Font::new(8, 8, 256).fits_hex());
+        assert_eq!(Font::from_hex("0041:00\n").err().unwrap().line, 1);
+        assert_eq!(Font::from_hex("\nZZ:00\n").err().unwrap().line, 2);
+        assert_eq!(Font::from_hex(&HEX.replace("0041", "110000")).err().unwrap().line, 1);
+    }
+}
@@ -0,0 +1,418 @@
+use std::fmt::{self, Write};
+
+use super::{write_string, BitMatrix, Font};
+
+// `struct font_desc` fields that psfe writes itself, from the font and its desc
+const GENERATED_FIELDS: [&str; 6] = ["idx", "name", "width", "height", "charcount", "data"];
+
+// Names from the `struct font_desc` of a lib/fonts/font_*.c file, so a font that is edited
+// and written back keeps the symbols the rest of the kernel refers to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KernelFontDesc {
+    pub symbol: String,
+    pub data_symbol: String,
+    pub idx: String,
+    pub name: String,
+    // Initializer lines other than the generated fields, like `.pref` and any preprocessor
+    // conditionals around it, written back after them as they were
+    pub extra_lines: Vec<String>,
+    pub exported: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct KernelFontError {
+    pub message: String,
+}
+
+impl fmt::Display for KernelFontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for KernelFontError {}
+
+impl KernelFontDesc {
+    // Kernel fonts named after their size have an index like FONT6x11_IDX
+    pub fn from_name(name: &str) -> Self {
+        let ident: String =
+            name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let idx = if ident.starts_with(|c: char| c.is_ascii_digit()) {
+            format!("FONT{ident}_IDX")
+        } else {
+            format!("{ident}_IDX")
+        };
+        let lower = ident.to_ascii_lowercase();
+
+        Self {
+            symbol: format!("font_{lower}"),
+            data_symbol: format!("fontdata_{lower}"),
+            idx,
+            name: name.to_string(),
+            extra_lines: vec!["\t.pref\t= 0,".to_string()],
+            exported: false,
+        }
+    }
+
+    pub fn from_source(text: &str) -> Option<Self> {
+        let text = strip_comments(text);
+        let (symbol, body) = font_desc(&text)?;
+        let fields = desc_fields(body);
+        let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, v)| v);
+
+        let data = field("data")?;
+        let data_symbol = data.strip_suffix(".data").unwrap_or(data);
+
+        Some(Self {
+            symbol: symbol.to_string(),
+            data_symbol: data_symbol.trim_start_matches('&').to_string(),
+            idx: field("idx")?.clone(),
+            name: field("name")?.trim_matches('"').to_string(),
+            extra_lines: body
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.trim().is_empty() && !is_generated_field(line))
+                .map(str::to_string)
+                .collect(),
+            exported: text.contains(&format!("EXPORT_SYMBOL({symbol})")),
+        })
+    }
+}
+
+impl Font {
+    // Reads both the current layout with a `struct font_data` and its extra header words,
+    // and the older plain `unsigned char` array
+    pub fn from_kernel_source(text: &str) -> Result<Self, KernelFontError> {
+        let text = strip_comments(text);
+        let (_, body) = font_desc(&text).ok_or_else(|| error("no struct font_desc found"))?;
+        let fields = desc_fields(body);
+        let number = |name: &str| -> Result<Option<usize>, KernelFontError> {
+            let Some((_, value)) = fields.iter().find(|(key, _)| key == name) else {
+                return Ok(None);
+            };
+
+            let value = value.parse().map_err(|_| error(&format!("bad .{name} \"{value}\"")))?;
+            Ok(Some(value))
+        };
+
+        let width = number("width")?.ok_or_else(|| error("missing .width"))?;
+        let height = number("height")?.ok_or_else(|| error("missing .height"))?;
+        let size = |size: usize| u8::try_from(size).ok().filter(|&size| size > 0);
+        let (Some(width), Some(height)) = (size(width), size(height)) else {
+            return Err(error(&format!("unsupported size {width}x{height}")));
+        };
+
+        let data = font_data(&text)?;
+        let glyph_size = (width as usize).div_ceil(8) * height as usize;
+        let num_glyphs = match number("charcount")? {
+            Some(count) => count,
+            None => data.len() / glyph_size,
+        };
+
+        if num_glyphs == 0 {
+            return Err(error("font has no glyphs"));
+        }
+
+        if num_glyphs.checked_mul(glyph_size).is_none_or(|size| data.len() < size) {
+            let message = format!("{} bytes of data for {num_glyphs} glyphs", data.len());
+            return Err(error(&message));
+        }
+
+        let mut font = Font::new(width, height, 0);
+        font.glyphs = data
+            .chunks(glyph_size)
+            .take(num_glyphs)
+            .map(|rows| BitMatrix::deserialize(width, height, rows))
+            .collect();
+        font.version = if font.fits_psf1() { 1 } else { 2 };
+
+        Ok(font)
+    }
+
+    pub fn construct_kernel_source(&self, desc: &KernelFontDesc) -> String {
+        write_string(|out| self.write_kernel_source(out, desc))
+    }
+
+    fn write_kernel_source(&self, out: &mut String, desc: &KernelFontDesc) -> fmt::Result {
+        let bytes_per_row = (self.width as usize).div_ceil(8);
+        let data_size = bytes_per_row * self.height as usize * self.glyphs.len();
+
+        writeln!(out, "/* Generated by psfe */\n")?;
+        writeln!(out, "#include <linux/font.h>")?;
+        writeln!(out, "#include <linux/module.h>\n")?;
+        writeln!(out, "#define FONTDATAMAX {data_size}\n")?;
+        writeln!(out, "static const struct font_data {} = {{", desc.data_symbol)?;
+        writeln!(out, "\t{{ 0, 0, FONTDATAMAX, 0 }}, {{")?;
+
+        for (idx, glyph) in self.glyphs.iter().enumerate() {
+            writeln!(out, "\t/* {} */", glyph_title(idx))?;
+
+            for (y, row) in glyph.serialize().chunks(bytes_per_row).enumerate() {
+                let bits: String = (0..glyph.width as usize)
+                    .map(|x| if glyph.get(x, y) { '1' } else { '0' })
+                    .collect();
+
+                let bytes: Vec<_> = row.iter().map(|byte| format!("0x{byte:02x},")).collect();
+                writeln!(out, "\t{} /* {bits} */", bytes.join(" "))?;
+            }
+
+            if idx + 1 < self.glyphs.len() {
+                writeln!(out)?;
+            }
+        }
+
+        writeln!(out, "}} }};\n")?;
+        writeln!(out, "const struct font_desc {} = {{", desc.symbol)?;
+        writeln!(out, "\t.idx\t= {},", desc.idx)?;
+        writeln!(out, "\t.name\t= \"{}\",", desc.name)?;
+        writeln!(out, "\t.width\t= {},", self.width)?;
+        writeln!(out, "\t.height\t= {},", self.height)?;
+        writeln!(out, "\t.charcount = {},", self.glyphs.len())?;
+        writeln!(out, "\t.data\t= {}.data,", desc.data_symbol)?;
+
+        for line in &desc.extra_lines {
+            writeln!(out, "{line}")?;
+        }
+
+        writeln!(out, "}};")?;
+
+        if desc.exported {
+            writeln!(out, "EXPORT_SYMBOL({});", desc.symbol)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Glyphs are titled like the kernel's own sources, e.g. `65 0x41 'A'` or `1 0x01 '^A'`
+fn glyph_title(idx: usize) -> String {
+    let character = match idx {
+        0..=31 => format!(" '^{}'", char::from(idx as u8 + 64)),
+        32..=126 => format!(" '{}'", char::from(idx as u8)),
+        127 => " '^?'".to_string(),
+        _ => String::new(),
+    };
+
+    format!("{idx} 0x{idx:02x}{character}")
+}
+
+// Replaces comments with spaces, string literals are left alone so names survive
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(['/', '"']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let (kept, skipped) = if rest.starts_with("/*") {
+            (" ", rest.find("*/").map_or(rest.len(), |end| end + 2))
+        } else if rest.starts_with("//") {
+            (" ", rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(literal) = rest.strip_prefix('"') {
+            let end = literal.find('"').map_or(rest.len(), |end| end + 2);
+            (&rest[..end], end)
+        } else {
+            ("/", 1)
+        };
+
+        out.push_str(kept);
+        rest = &rest[skipped..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+// Returns the symbol and the initializer of the first `struct font_desc`
+fn font_desc(text: &str) -> Option<(&str, &str)> {
+    let decl = &text[text.find("struct font_desc")? + "struct font_desc".len()..];
+    let (symbol, body) = decl.split_once('=')?;
+    let (body, _) = brace_block(body)?;
+
+    Some((symbol.trim(), body))
+}
+
+// Designated initializers like `.width = 8`. Preprocessor lines are skipped, so the fields
+// of every branch of an #ifdef are read and the first one wins
+fn desc_fields(body: &str) -> Vec<(String, String)> {
+    body.split(',')
+        .filter_map(|field| {
+            let lines: Vec<_> = field.lines().filter(|line| !is_preprocessor(line)).collect();
+            let field = lines.join("\n");
+            let (key, value) = field.trim().strip_prefix('.')?.split_once('=')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn is_generated_field(line: &str) -> bool {
+    let field = line.trim().strip_prefix('.').and_then(|field| field.split_once('='));
+    field.is_some_and(|(key, _)| GENERATED_FIELDS.contains(&key.trim()))
+}
+
+fn is_preprocessor(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+fn font_data(text: &str) -> Result<Vec<u8>, KernelFontError> {
+    let start = text
+        .find("struct font_data")
+        .or_else(|| text.find("unsigned char"))
+        .ok_or_else(|| error("no font data array found"))?;
+    let (_, initializer) =
+        text[start..].split_once('=').ok_or_else(|| error("font data is not initialized"))?;
+    let (mut body, _) = brace_block(initializer).ok_or_else(|| error("unterminated font data"))?;
+
+    // `struct font_data` starts with the extra header words in their own braces
+    if body.trim_start().starts_with('{') {
+        let data = brace_block(body).and_then(|(_, after_extra)| brace_block(after_extra));
+        (body, _) = data.ok_or_else(|| error("missing font data after the header"))?;
+    }
+
+    body.split(',')
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            let value = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+                Some(hex) => u8::from_str_radix(hex, 16),
+                None => token.parse(),
+            };
+
+            value.map_err(|_| error(&format!("bad font data byte \"{token}\"")))
+        })
+        .collect()
+}
+
+// Splits `text` into the contents of its first brace block and whatever follows the block
+fn brace_block(text: &str) -> Option<(&str, &str)> {
+    let start = text.find('{')? + 1;
+    let mut depth = 1;
+
+    for (offset, c) in text[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 1 => {
+                return Some((&text[start..start + offset], &text[start + offset + 1..]))
+            }
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+
+    None
+}
+
+fn error(message: &str) -> KernelFontError {
+    KernelFontError {
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed down version of lib/fonts/font_8x8.c with two glyphs
+    const FONT_8X8: &str = "\
+// SPDX-License-Identifier: GPL-2.0
+#include <linux/font.h>
+
+#define FONTDATAMAX 16
+
+static const struct font_data fontdata_8x8 = {
+\t{ 0, 0, FONTDATAMAX, 0 }, {
+\t/* 0 0x00 '^@' */
+\t0x00, /* 00000000 */
+\t0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+
+\t/* 1 0x01 '^A' */
+\t0x7e, /* 01111110 */
+\t0x81, /* 10000001 */
+\t0xa5, 0x81, 0xbd, 0x99, 0x81, 0x7e,
+} };
+
+const struct font_desc font_vga_8x8 = {
+\t.idx\t= VGA8x8_IDX,
+\t.name\t= \"VGA8x8\",
+\t.width\t= 8,
+\t.height\t= 8,
+\t.charcount = 2,
+\t.data\t= fontdata_8x8.data,
+\t.pref\t= 0,
+};
+";
+
+    #[test]
+    fn parse_kernel_font() {
+        let font = Font::from_kernel_source(FONT_8X8).unwrap();
+
+        assert_eq!((font.width, font.height, font.glyphs.len()), (8, 8, 2));
+        assert_eq!(font.glyphs[1].serialize(), [0x7e, 0x81, 0xa5, 0x81, 0xbd, 0x99, 0x81, 0x7e]);
+
+        let desc = KernelFontDesc::from_source(FONT_8X8).unwrap();
+        assert_eq!(desc.symbol, "font_vga_8x8");
+        assert_eq!(desc.data_symbol, "fontdata_8x8");
+        assert_eq!((desc.idx.as_str(), desc.name.as_str()), ("VGA8x8_IDX", "VGA8x8"));
+        assert!(!desc.exported);
+
+        // Pre-5.9 kernels had a plain array without the extra header
+        let old = FONT_8X8
+            .replace(
+                "struct font_data fontdata_8x8 = {\n\t{ 0, 0, FONTDATAMAX, 0 }, {",
+                "unsigned char fontdata_8x8[FONTDATAMAX] = {",
+            )
+            .replace("} };", "};")
+            .replace("\t.charcount = 2,\n", "");
+        assert!(Font::from_kernel_source(&old).unwrap().glyphs == font.glyphs);
+    }
+
+    // lib/fonts/font_sun12x22.c and font_acorn_8x8.c pick their preference per architecture
+    #[test]
+    fn keep_conditional_fields() {
+        let pref = "#ifdef __sparc__\n\t.pref\t= 5,\n#else\n\t.pref\t= -1,\n#endif";
+        let text = FONT_8X8.replace("\t.pref\t= 0,", pref);
+
+        let desc = KernelFontDesc::from_source(&text).unwrap();
+        assert_eq!(desc.extra_lines, pref.lines().collect::<Vec<_>>());
+        assert_eq!(desc.name, "VGA8x8");
+
+        let font = Font::from_kernel_source(&text).unwrap();
+        let source = font.construct_kernel_source(&desc);
+        assert!(source.contains(&format!("\t.data\t= fontdata_8x8.data,\n{pref}\n}};\n")));
+        assert_eq!(KernelFontDesc::from_source(&source), Some(desc));
+
+        // A field after a conditional is still read
+        let text = FONT_8X8.replace("\t.charcount = 2,\n", "#if 1\n#endif\n\t.charcount = 1,\n");
+        assert_eq!(Font::from_kernel_source(&text).unwrap().glyphs.len(), 1);
+    }
+
+    #[test]
+    fn write_kernel_font() {
+        let font = Font::from_kernel_source(FONT_8X8).unwrap();
+        let desc = KernelFontDesc::from_source(FONT_8X8).unwrap();
+        let source = font.construct_kernel_source(&desc);
+
+        assert!(source.contains("#define FONTDATAMAX 16\n"));
+        assert!(source.contains("\t{ 0, 0, FONTDATAMAX, 0 }, {\n\t/* 0 0x00 '^@' */\n"));
+        assert!(source.contains("\t0x81, /* 10000001 */\n"));
+        assert!(source.contains("const struct font_desc font_vga_8x8 = {\n\t.idx\t= VGA8x8_IDX,\n"));
+
+        let parsed = Font::from_kernel_source(&source).unwrap();
+        assert!(parsed.glyphs == font.glyphs);
+        assert_eq!(KernelFontDesc::from_source(&source), Some(desc));
+
+        let desc = KernelFontDesc::from_name("6x11");
+        assert_eq!(desc.idx, "FONT6x11_IDX");
+        let source = font.construct_kernel_source(&desc);
+        assert!(source.contains("\t.data\t= fontdata_6x11.data,\n\t.pref\t= 0,\n};"));
+        assert!(Font::from_kernel_source("int main;").is_err());
+
+        let empty = FONT_8X8.replace("charcount = 2", "charcount = 0");
+        assert!(Font::from_kernel_source(&empty).is_err());
+        let no_width = FONT_8X8.replace("width\t= 8", "width\t= 0");
+        assert!(Font::from_kernel_source(&no_width).is_err());
+        let huge = FONT_8X8.replace("charcount = 2", &format!("charcount = {}", usize::MAX / 4));
+        assert!(Font::from_kernel_source(&huge).is_err());
+    }
+}
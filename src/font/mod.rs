@@ -0,0 +1,395 @@
+mod bdf;
+mod hex;
+mod kernel;
+mod psf;
+mod source;
+mod text;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use bdf::BdfMapping;
+pub use kernel::{KernelFontDesc, KernelFontError};
+pub use psf::{ParseError, Psf2Header, PsfHeader};
+pub use source::SourceOptions;
+
+#[derive(Clone)]
+pub struct Font {
+    pub version: u8,
+    pub psf1_mode: u8,
+    // PSF2 header fields and the bytes between the header and the glyphs, kept so a loaded
+    // file is written back as it was. A charsize of 0 writes the smallest one
+    pub psf2_version: u32,
+    pub psf2_flags: u32,
+    pub psf2_charsize: u32,
+    pub psf2_header_extra: Vec<u8>,
+    pub width: u8,
+    pub height: u8,
+    pub glyphs: Vec<BitMatrix>,
+    pub unicode_table: Option<Vec<UnicodeEntry>>,
+    // Free-form notes by glyph index, only the text format keeps them
+    pub notes: BTreeMap<usize, String>,
+    // Glyphs narrower than the font by index, hex files mix halfwidth and fullwidth ones
+    pub glyph_widths: BTreeMap<usize, u8>,
+}
+
+// Errors of the line based formats, BDF, hex and text
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Default)]
+pub struct UnicodeEntry {
+    pub codepoints: Vec<u32>,
+    pub sequences: Vec<Vec<u32>>,
+}
+
+// Which side of a glyph gains or loses rows or columns when it is resized, Center splits
+// them between both sides with the odd one going to the end
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct BitMatrix {
+    // This is a pretty wasteful structure, but it simplifies things
+    width: u8,
+    height: u8,
+    data: Vec<bool>,
+}
+
+impl Font {
+    pub fn new(width: u8, height: u8, num_glyphs: usize) -> Self {
+        Self {
+            version: if width == 8 { 1 } else { 2 },
+            psf1_mode: 0,
+            psf2_version: 0,
+            psf2_flags: 0,
+            psf2_charsize: 0,
+            psf2_header_extra: vec![],
+            width,
+            height,
+            glyphs: (0..num_glyphs).map(|_| BitMatrix::new(width, height)).collect(),
+            unicode_table: None,
+            notes: BTreeMap::new(),
+            glyph_widths: BTreeMap::new(),
+        }
+    }
+
+    pub fn unicode_entry(&self, idx: usize) -> Option<&UnicodeEntry> {
+        self.unicode_table.as_ref().and_then(|table| table.get(idx))
+    }
+
+    // Without a unicode table glyphs are indexed by codepoint directly
+    pub fn glyph_index(&self, codepoint: u32) -> Option<usize> {
+        match &self.unicode_table {
+            Some(table) => table.iter().position(|entry| entry.codepoints.contains(&codepoint)),
+            None => Some(codepoint as usize).filter(|&idx| idx < self.glyphs.len()),
+        }
+    }
+
+    pub fn unicode_entry_mut(&mut self, idx: usize) -> &mut UnicodeEntry {
+        let num_glyphs = self.glyphs.len();
+        let table = self.unicode_table.get_or_insert_with(|| {
+            std::iter::repeat_with(UnicodeEntry::default).take(num_glyphs).collect()
+        });
+
+        &mut table[idx]
+    }
+
+    pub fn decrease_height(&mut self) {
+        if self.height > 1 {
+            self.resize(self.width, self.height - 1, Edge::End, Edge::Start);
+        }
+    }
+
+    pub fn resize(&mut self, width: u8, height: u8, columns_at: Edge, rows_at: Edge) {
+        for glyph in &mut self.glyphs {
+            *glyph = glyph.resized(width, height, columns_at, rows_at);
+        }
+
+        self.width = width;
+        self.height = height;
+        // Padding that fit the old size has no meaning for the new one
+        self.psf2_charsize = 0;
+    }
+}
+
+impl LineError {
+    fn new(line: usize, message: &str) -> Self {
+        Self {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for LineError {}
+
+impl Edge {
+    // How far the existing pixels move when a side goes from one size to another
+    pub fn offset(self, from: u8, to: u8) -> i32 {
+        let grown = i32::from(to) - i32::from(from);
+
+        match self {
+            Edge::Start => grown,
+            Edge::Center => grown / 2,
+            Edge::End => 0,
+        }
+    }
+}
+
+// Writing into a String can't fail
+fn write_string(write: impl FnOnce(&mut String) -> fmt::Result) -> String {
+    let mut out = String::new();
+    write(&mut out).unwrap();
+    out
+}
+
+impl BitMatrix {
+    pub fn new(width: u8, height: u8) -> Self {
+        let mut data = Vec::new();
+        let w: usize = width.into();
+        let h: usize = height.into();
+
+        data.resize(w * h, false);
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    pub fn set_to(&mut self, x: usize, y: usize, val: bool) {
+        assert!(x < self.width.into());
+        assert!(y < self.height.into());
+
+        let w: usize = self.width.into();
+
+        self.data[y * w + x] = val;
+    }
+
+    pub fn set(&mut self, x: usize, y: usize) {
+        self.set_to(x, y, true);
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        assert!(x < self.width.into());
+        assert!(y < self.height.into());
+
+        let w: usize = self.width.into();
+
+        self.data[y * w + x]
+    }
+
+    pub fn clear_all(&mut self) {
+        self.data.fill(false);
+    }
+
+    pub fn deserialize(width: u8, height: u8, rows: &[u8]) -> Self {
+        let mut matrix = Self::new(width, height);
+        let bytes_per_row = (width as usize).div_ceil(8);
+
+        for (y, row) in rows.chunks(bytes_per_row).take(height.into()).enumerate() {
+            for x in 0..width as usize {
+                let mask = 0x80 >> (x % 8);
+
+                if row[x / 8] & mask != 0 {
+                    matrix.set(x, y);
+                }
+            }
+        }
+
+        matrix
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let w: usize = self.width.into();
+        let bytes_per_row = w.div_ceil(8);
+        let mut rows = vec![];
+
+        for y in 0..self.height.into() {
+            let mut row = vec![0; bytes_per_row];
+
+            for x in 0..w {
+                let bit = self.get(x, y);
+                row[x / 8] |= u8::from(bit) << (8 - x % 8 - 1);
+            }
+
+            rows.extend(row);
+        }
+
+        rows
+    }
+
+    pub fn invert(&mut self) {
+        for pixel in &mut self.data {
+            *pixel = !*pixel;
+        }
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        for row in self.data.chunks_mut(self.width.into()) {
+            row.reverse();
+        }
+    }
+
+    pub fn flip_vertical(&mut self) {
+        let rows = self.data.chunks(self.width.into()).rev();
+        self.data = rows.flatten().copied().collect();
+    }
+
+    // Clockwise, only square matrices can be rotated in place
+    pub fn rotate(&mut self) {
+        assert_eq!(self.width, self.height);
+
+        let size: usize = self.width.into();
+        let source = self.clone();
+
+        for y in 0..size {
+            for x in 0..size {
+                self.set_to(x, y, source.get(y, size - 1 - x));
+            }
+        }
+    }
+
+    // Pixels pushed over an edge come back on the other side when wrapping, otherwise they
+    // are dropped and the pixels shifted in are empty
+    pub fn shift(&mut self, dx: i32, dy: i32, wrap: bool) {
+        let (w, h) = (self.width as i32, self.height as i32);
+        let source = self.clone();
+
+        for y in 0..h {
+            for x in 0..w {
+                let (mut src_x, mut src_y) = (x - dx, y - dy);
+
+                if wrap {
+                    src_x = src_x.rem_euclid(w);
+                    src_y = src_y.rem_euclid(h);
+                }
+
+                let inside = (0..w).contains(&src_x) && (0..h).contains(&src_y);
+                let set = inside && source.get(src_x as usize, src_y as usize);
+
+                self.set_to(x as usize, y as usize, set);
+            }
+        }
+    }
+
+    // Pixels pushed past the new edges are dropped and new rows and columns are empty
+    pub fn resized(&self, width: u8, height: u8, columns_at: Edge, rows_at: Edge) -> Self {
+        let mut matrix = Self::new(width, height);
+        let dx = columns_at.offset(self.width, width);
+        let dy = rows_at.offset(self.height, height);
+
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let (src_x, src_y) = (x - dx, y - dy);
+
+                if (0..self.width as i32).contains(&src_x)
+                    && (0..self.height as i32).contains(&src_y)
+                {
+                    matrix.set_to(x as usize, y as usize, self.get(src_x as usize, src_y as usize));
+                }
+            }
+        }
+
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(glyph: &BitMatrix) -> Vec<String> {
+        (0..glyph.height as usize)
+            .map(|y| {
+                (0..glyph.width as usize).map(|x| if glyph.get(x, y) { '#' } else { '.' }).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resize_edges() {
+        let mut glyph = BitMatrix::new(3, 2);
+        glyph.set(0, 0);
+        glyph.set(2, 1);
+
+        let padded = glyph.resized(4, 4, Edge::End, Edge::Start);
+        assert_eq!(rows(&padded), ["....", "....", "#...", "..#."]);
+
+        let centered = glyph.resized(6, 5, Edge::Center, Edge::Center);
+        assert_eq!(rows(&centered), ["......", ".#....", "...#..", "......", "......"]);
+
+        assert_eq!(rows(&glyph.resized(2, 1, Edge::End, Edge::End)), ["#."]);
+        assert_eq!(rows(&glyph.resized(2, 1, Edge::Start, Edge::Start)), [".#"]);
+
+        // 8x14 padded to 8x16 with a row on both sides
+        let mut font = Font::new(8, 14, 256);
+        font.glyphs[65].set(7, 13);
+        font.resize(8, 16, Edge::End, Edge::Center);
+        assert_eq!((font.width, font.height), (8, 16));
+        assert!(font.glyphs[65].get(7, 14) && !font.glyphs[65].get(7, 15));
+
+        font.decrease_height();
+        assert_eq!(font.height, 15);
+        assert!(font.glyphs[65].get(7, 13));
+
+        let mut font = Font::new(8, 1, 1);
+        font.decrease_height();
+        assert_eq!(font.height, 1);
+    }
+    #[test]
+    fn transforms() {
+        let mut glyph = BitMatrix::new(3, 3);
+        glyph.set(0, 0);
+        glyph.set(1, 0);
+        glyph.set(2, 1);
+
+        let mut flipped = glyph.clone();
+        flipped.flip_horizontal();
+        assert_eq!(rows(&flipped), [".##", "#..", "..."]);
+        flipped.flip_vertical();
+        assert_eq!(rows(&flipped), ["...", "#..", ".##"]);
+
+        let mut rotated = glyph.clone();
+        rotated.rotate();
+        assert_eq!(rows(&rotated), ["..#", "..#", ".#."]);
+
+        (0..3).for_each(|_| rotated.rotate());
+        assert!(rotated == glyph);
+
+        let mut inverted = glyph.clone();
+        inverted.invert();
+        assert_eq!(rows(&inverted), ["..#", "##.", "###"]);
+
+        let mut shifted = glyph.clone();
+        shifted.shift(1, 0, false);
+        assert_eq!(rows(&shifted), [".##", "...", "..."]);
+
+        let mut wrapped = glyph.clone();
+        wrapped.shift(1, -1, true);
+        assert_eq!(rows(&wrapped), ["#..", "...", ".##"]);
+    }
+}
@@ -0,0 +1,794 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::{BitMatrix, Font, UnicodeEntry};
+
+const PSF1_MAGIC0: u8 = 0x36;
+const PSF1_MAGIC1: u8 = 0x04;
+const PSF1_HEADER_SIZE: usize = 4;
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_MODEHASSEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xffff;
+const PSF1_STARTSEQ: u16 = 0xfffe;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HEADER_SIZE: u32 = 32;
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_STARTSEQ: u8 = 0xfe;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    BadMagic,
+    TruncatedHeader {
+        offset: usize,
+        expected: usize,
+    },
+    TruncatedGlyphData {
+        glyph: usize,
+        offset: usize,
+    },
+    BadUnicodeTable {
+        glyph: usize,
+        offset: usize,
+    },
+    UnsupportedDimensions {
+        width: u32,
+        height: u32,
+        glyphs: u32,
+    },
+}
+
+enum UnicodeToken {
+    Codepoint(u32),
+    StartSequence,
+    Separator,
+}
+
+// A PSF header as it is in the file, before any of it is checked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PsfHeader {
+    Psf1 { mode: u8, charsize: u8 },
+    Psf2(Psf2Header),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Psf2Header {
+    pub version: u32,
+    pub headersize: u32,
+    pub flags: u32,
+    pub length: u32,
+    pub charsize: u32,
+    pub height: u32,
+    pub width: u32,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a PSF1 or PSF2 font (bad magic)"),
+            Self::TruncatedHeader { offset, expected } => {
+                write!(f, "header truncated at byte {offset}, expected {expected} bytes")
+            }
+            Self::TruncatedGlyphData { glyph, offset } => {
+                write!(f, "glyph data truncated at byte {offset} (glyph {glyph})")
+            }
+            Self::BadUnicodeTable { glyph, offset } => {
+                write!(f, "bad unicode table entry at byte {offset} (glyph {glyph})")
+            }
+            Self::UnsupportedDimensions {
+                width,
+                height,
+                glyphs,
+            } => write!(f, "unsupported dimensions {width}x{height} with {glyphs} glyphs"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Font {
+    pub fn from_file(file: &[u8]) -> Result<Self, ParseError> {
+        match PsfHeader::parse(file)? {
+            PsfHeader::Psf1 { mode, charsize } => Self::parse_psf1(file, mode, charsize),
+            PsfHeader::Psf2(header) => Self::parse_psf2(file, &header),
+        }
+    }
+
+    pub fn fits_psf1(&self) -> bool {
+        let fits_ucs2 = |codepoint: &u32| *codepoint < u32::from(PSF1_STARTSEQ);
+        let table_fits = self.unicode_table.iter().flatten().all(|entry| {
+            entry.codepoints.iter().all(fits_ucs2)
+                && entry.sequences.iter().flatten().all(fits_ucs2)
+        });
+
+        self.width == 8 && matches!(self.glyphs.len(), 256 | 512) && table_fits
+    }
+
+    pub fn save_version(&self) -> u8 {
+        if self.fits_psf1() {
+            self.version
+        } else {
+            2
+        }
+    }
+
+    pub fn construct_font_file(&self) -> Vec<u8> {
+        if self.save_version() == 1 {
+            self.construct_psf1_file()
+        } else {
+            self.construct_psf2_file()
+        }
+    }
+
+    fn construct_psf1_file(&self) -> Vec<u8> {
+        let mut mode = self.psf1_mode & !PSF1_MODE512;
+
+        if self.glyphs.len() == 512 {
+            mode |= PSF1_MODE512;
+        }
+
+        if let Some(table) = &self.unicode_table {
+            if mode & (PSF1_MODEHASTAB | PSF1_MODEHASSEQ) == 0 {
+                mode |= PSF1_MODEHASTAB;
+            }
+
+            if table.iter().any(|entry| !entry.sequences.is_empty()) {
+                mode |= PSF1_MODEHASSEQ;
+            }
+        } else {
+            mode &= !(PSF1_MODEHASTAB | PSF1_MODEHASSEQ);
+        }
+
+        let mut file = vec![
+            PSF1_MAGIC0,
+            PSF1_MAGIC1,
+            mode,
+            self.height, // charsize
+        ];
+
+        for glyph in &self.glyphs {
+            file.extend(glyph.serialize());
+        }
+
+        if let Some(table) = &self.unicode_table {
+            for token in table.iter().flat_map(UnicodeEntry::tokens) {
+                let value = match token {
+                    UnicodeToken::Codepoint(codepoint) => codepoint.try_into().unwrap(),
+                    UnicodeToken::StartSequence => PSF1_STARTSEQ,
+                    UnicodeToken::Separator => PSF1_SEPARATOR,
+                };
+
+                file.extend(value.to_le_bytes());
+            }
+        }
+
+        file
+    }
+
+    fn construct_psf2_file(&self) -> Vec<u8> {
+        let width = u32::from(self.width);
+        let height = u32::from(self.height);
+        let mut flags = self.psf2_flags & !PSF2_HAS_UNICODE_TABLE;
+
+        if self.unicode_table.is_some() {
+            flags |= PSF2_HAS_UNICODE_TABLE;
+        }
+
+        let header_extra: u32 = self.psf2_header_extra.len().try_into().unwrap();
+        let header = Psf2Header {
+            version: self.psf2_version,
+            headersize: PSF2_HEADER_SIZE + header_extra,
+            flags,
+            length: self.glyphs.len().try_into().unwrap(),
+            charsize: self.psf2_charsize.max(width.div_ceil(8) * height),
+            height,
+            width,
+        };
+
+        let mut file = header.serialize();
+        file.extend(&self.psf2_header_extra);
+
+        for glyph in &self.glyphs {
+            let mut rows = glyph.serialize();
+            rows.resize(header.charsize as usize, 0);
+            file.extend(rows);
+        }
+
+        if let Some(table) = &self.unicode_table {
+            for token in table.iter().flat_map(UnicodeEntry::tokens) {
+                match token {
+                    UnicodeToken::Codepoint(codepoint) => file.extend(encode_utf8(codepoint)),
+                    UnicodeToken::StartSequence => file.push(PSF2_STARTSEQ),
+                    UnicodeToken::Separator => file.push(PSF2_SEPARATOR),
+                }
+            }
+        }
+
+        file
+    }
+
+    // PSF1 glyphs are a byte wide, so the charsize is the height
+    fn parse_psf1(file: &[u8], mode: u8, height: u8) -> Result<Self, ParseError> {
+        let num_glyphs = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+
+        if height == 0 {
+            return Err(ParseError::UnsupportedDimensions {
+                width: 8,
+                height: 0,
+                glyphs: num_glyphs,
+            });
+        }
+
+        let num_glyphs = num_glyphs as usize;
+        let charsize = height as usize;
+        let glyphs = parse_glyphs(file, PSF1_HEADER_SIZE, num_glyphs, charsize, 8, height)?;
+
+        let unicode_table = if mode & (PSF1_MODEHASTAB | PSF1_MODEHASSEQ) == 0 {
+            None
+        } else {
+            let table_offset = PSF1_HEADER_SIZE + num_glyphs * charsize;
+            Some(Self::parse_psf1_unicode_table(file, table_offset, num_glyphs)?)
+        };
+
+        Ok(Self {
+            version: 1,
+            psf1_mode: mode,
+            psf2_version: 0,
+            psf2_flags: 0,
+            psf2_charsize: 0,
+            psf2_header_extra: vec![],
+            width: 8,
+            height,
+            glyphs,
+            unicode_table,
+            notes: BTreeMap::new(),
+            glyph_widths: BTreeMap::new(),
+        })
+    }
+
+    fn parse_psf1_unicode_table(
+        file: &[u8],
+        table_offset: usize,
+        num_glyphs: usize,
+    ) -> Result<Vec<UnicodeEntry>, ParseError> {
+        let table = &file[table_offset..];
+        let tokens = table.chunks_exact(2).enumerate().map(|(idx, bytes)| {
+            let token = match u16::from_le_bytes([bytes[0], bytes[1]]) {
+                PSF1_SEPARATOR => UnicodeToken::Separator,
+                PSF1_STARTSEQ => UnicodeToken::StartSequence,
+                value => UnicodeToken::Codepoint(value.into()),
+            };
+
+            (table_offset + idx * 2, Some(token))
+        });
+
+        UnicodeEntry::parse_table(tokens, num_glyphs, file.len())
+    }
+
+    fn parse_psf2(file: &[u8], header: &Psf2Header) -> Result<Self, ParseError> {
+        let headersize = header.headersize as usize;
+
+        if header.headersize < PSF2_HEADER_SIZE {
+            return Err(ParseError::TruncatedHeader {
+                offset: headersize,
+                expected: PSF2_HEADER_SIZE as usize,
+            });
+        }
+
+        if headersize > file.len() {
+            return Err(ParseError::TruncatedHeader {
+                offset: file.len(),
+                expected: headersize,
+            });
+        }
+
+        let unsupported = ParseError::UnsupportedDimensions {
+            width: header.width,
+            height: header.height,
+            glyphs: header.length,
+        };
+
+        let (Ok(width), Ok(height)) = (u8::try_from(header.width), u8::try_from(header.height))
+        else {
+            return Err(unsupported);
+        };
+
+        let min_charsize = header.width.div_ceil(8) * header.height;
+
+        if width == 0 || height == 0 || header.length == 0 || header.charsize < min_charsize {
+            return Err(unsupported);
+        }
+
+        let num_glyphs = header.length as usize;
+        let charsize = header.charsize as usize;
+        let glyphs = parse_glyphs(file, headersize, num_glyphs, charsize, width, height)?;
+
+        let unicode_table = if header.flags & PSF2_HAS_UNICODE_TABLE == 0 {
+            None
+        } else {
+            let table_offset = headersize + num_glyphs * charsize;
+            Some(Self::parse_psf2_unicode_table(file, table_offset, num_glyphs)?)
+        };
+
+        Ok(Self {
+            version: 2,
+            psf1_mode: 0,
+            psf2_version: header.version,
+            psf2_flags: header.flags,
+            psf2_charsize: header.charsize,
+            psf2_header_extra: file[PSF2_HEADER_SIZE as usize..headersize].to_vec(),
+            width,
+            height,
+            glyphs,
+            unicode_table,
+            notes: BTreeMap::new(),
+            glyph_widths: BTreeMap::new(),
+        })
+    }
+
+    fn parse_psf2_unicode_table(
+        file: &[u8],
+        table_offset: usize,
+        num_glyphs: usize,
+    ) -> Result<Vec<UnicodeEntry>, ParseError> {
+        let mut offset = table_offset;
+
+        let tokens = std::iter::from_fn(|| {
+            let token_offset = offset;
+            let rest = &file[offset..];
+
+            let (token, len) = match *rest.first()? {
+                PSF2_SEPARATOR => (Some(UnicodeToken::Separator), 1),
+                PSF2_STARTSEQ => (Some(UnicodeToken::StartSequence), 1),
+                _ => match decode_utf8(rest) {
+                    Some((codepoint, len)) => (Some(UnicodeToken::Codepoint(codepoint)), len),
+                    None => (None, 1),
+                },
+            };
+
+            offset += len;
+
+            Some((token_offset, token))
+        });
+
+        UnicodeEntry::parse_table(tokens, num_glyphs, file.len())
+    }
+}
+
+impl UnicodeEntry {
+    fn parse_table(
+        mut tokens: impl Iterator<Item = (usize, Option<UnicodeToken>)>,
+        num_glyphs: usize,
+        end: usize,
+    ) -> Result<Vec<Self>, ParseError> {
+        let mut table = vec![];
+
+        for glyph in 0..num_glyphs {
+            let mut entry = Self::default();
+            let mut sequence: Option<Vec<u32>> = None;
+
+            loop {
+                let Some((offset, token)) = tokens.next() else {
+                    return Err(ParseError::BadUnicodeTable { glyph, offset: end });
+                };
+
+                match token.ok_or(ParseError::BadUnicodeTable { glyph, offset })? {
+                    UnicodeToken::Codepoint(codepoint) => match &mut sequence {
+                        Some(sequence) => sequence.push(codepoint),
+                        None => entry.codepoints.push(codepoint),
+                    },
+                    UnicodeToken::StartSequence => {
+                        if let Some(sequence) = sequence.replace(vec![]) {
+                            entry.sequences.push(sequence);
+                        }
+                    }
+                    UnicodeToken::Separator => break,
+                }
+            }
+
+            if let Some(sequence) = sequence {
+                entry.sequences.push(sequence);
+            }
+
+            table.push(entry);
+        }
+
+        Ok(table)
+    }
+
+    fn tokens(&self) -> Vec<UnicodeToken> {
+        let mut tokens = vec![];
+
+        for codepoint in &self.codepoints {
+            tokens.push(UnicodeToken::Codepoint(*codepoint));
+        }
+
+        for sequence in &self.sequences {
+            tokens.push(UnicodeToken::StartSequence);
+
+            for codepoint in sequence {
+                tokens.push(UnicodeToken::Codepoint(*codepoint));
+            }
+        }
+
+        tokens.push(UnicodeToken::Separator);
+
+        tokens
+    }
+}
+
+impl PsfHeader {
+    pub fn parse(file: &[u8]) -> Result<Self, ParseError> {
+        if file.starts_with(&[PSF1_MAGIC0, PSF1_MAGIC1]) {
+            let Some(&[_, _, mode, charsize]) = file.get(..PSF1_HEADER_SIZE) else {
+                return Err(ParseError::TruncatedHeader {
+                    offset: file.len(),
+                    expected: PSF1_HEADER_SIZE,
+                });
+            };
+
+            return Ok(Self::Psf1 { mode, charsize });
+        }
+
+        if file.starts_with(&PSF2_MAGIC) {
+            return Psf2Header::parse(file).map(Self::Psf2);
+        }
+
+        Err(ParseError::BadMagic)
+    }
+}
+
+impl Psf2Header {
+    fn parse(file: &[u8]) -> Result<Self, ParseError> {
+        let header_size = PSF2_HEADER_SIZE as usize;
+
+        if file.len() < header_size {
+            return Err(ParseError::TruncatedHeader {
+                offset: file.len(),
+                expected: header_size,
+            });
+        }
+
+        let field = |idx: usize| {
+            let offset = 4 + idx * 4;
+            let bytes = file[offset..offset + 4].try_into().unwrap();
+            u32::from_le_bytes(bytes)
+        };
+
+        Ok(Self {
+            version: field(0),
+            headersize: field(1),
+            flags: field(2),
+            length: field(3),
+            charsize: field(4),
+            height: field(5),
+            width: field(6),
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let fields = [
+            self.version,
+            self.headersize,
+            self.flags,
+            self.length,
+            self.charsize,
+            self.height,
+            self.width,
+        ];
+
+        let mut header = PSF2_MAGIC.to_vec();
+
+        for field in fields {
+            header.extend(field.to_le_bytes());
+        }
+
+        header
+    }
+}
+
+fn parse_glyphs(
+    file: &[u8],
+    offset: usize,
+    num_glyphs: usize,
+    charsize: usize,
+    width: u8,
+    height: u8,
+) -> Result<Vec<BitMatrix>, ParseError> {
+    let available = (file.len() - offset) / charsize;
+
+    if available < num_glyphs {
+        return Err(ParseError::TruncatedGlyphData {
+            glyph: available,
+            offset: offset + available * charsize,
+        });
+    }
+
+    let glyph_data = &file[offset..offset + num_glyphs * charsize];
+
+    Ok(glyph_data
+        .chunks(charsize)
+        .map(|rows| BitMatrix::deserialize(width, height, rows))
+        .collect())
+}
+
+fn encode_utf8(codepoint: u32) -> Vec<u8> {
+    let continuation = |shift: u32| 0x80 | ((codepoint >> shift) & 0x3f) as u8;
+
+    match codepoint {
+        0..=0x7f => vec![codepoint as u8],
+        0x80..=0x7ff => vec![0xc0 | (codepoint >> 6) as u8, continuation(0)],
+        0x800..=0xffff => vec![
+            0xe0 | (codepoint >> 12) as u8,
+            continuation(6),
+            continuation(0),
+        ],
+        _ => vec![
+            0xf0 | (codepoint >> 18) as u8,
+            continuation(12),
+            continuation(6),
+            continuation(0),
+        ],
+    }
+}
+
+fn decode_utf8(bytes: &[u8]) -> Option<(u32, usize)> {
+    let lead = *bytes.first()?;
+    let (len, initial) = match lead {
+        0x00..=0x7f => (1, lead),
+        0xc0..=0xdf => (2, lead & 0x1f),
+        0xe0..=0xef => (3, lead & 0x0f),
+        0xf0..=0xf7 => (4, lead & 0x07),
+        _ => return None,
+    };
+
+    let mut codepoint = u32::from(initial);
+
+    for &byte in bytes.get(1..len)? {
+        if byte & 0xc0 != 0x80 {
+            return None;
+        }
+
+        codepoint = (codepoint << 6) | u32::from(byte & 0x3f);
+    }
+
+    // Overlong and out of range encodings wouldn't survive a round trip
+    if codepoint > 0x10ffff || encode_utf8(codepoint).len() != len {
+        return None;
+    }
+
+    Some((codepoint, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Edge;
+
+    fn psf1_file(mode: u8) -> Vec<u8> {
+        let height = 14;
+        let num_glyphs = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+        let mut file = vec![PSF1_MAGIC0, PSF1_MAGIC1, mode, height];
+
+        for i in 0..num_glyphs * height as usize {
+            file.push((i * 37 % 251) as u8);
+        }
+
+        if mode & (PSF1_MODEHASTAB | PSF1_MODEHASSEQ) != 0 {
+            for glyph in 0..num_glyphs as u16 {
+                let mut values = vec![glyph];
+
+                if mode & PSF1_MODEHASSEQ != 0 && glyph % 3 == 0 {
+                    values.extend([PSF1_STARTSEQ, 0x41, 0x301, PSF1_STARTSEQ, 0x45, 0x300]);
+                }
+
+                values.push(PSF1_SEPARATOR);
+
+                for value in values {
+                    file.extend(value.to_le_bytes());
+                }
+            }
+        }
+
+        file
+    }
+
+    fn psf2_file(width: u32, num_glyphs: u32, with_table: bool) -> Vec<u8> {
+        let height = 16;
+        let bytes_per_row = width.div_ceil(8);
+        let header = Psf2Header {
+            version: 0,
+            headersize: PSF2_HEADER_SIZE,
+            flags: if with_table { PSF2_HAS_UNICODE_TABLE } else { 0 },
+            length: num_glyphs,
+            charsize: bytes_per_row * height,
+            height,
+            width,
+        };
+
+        let mut file = header.serialize();
+        let padding_mask = 0xffu8 << (7 - (width - 1) % 8);
+
+        for glyph in 0..num_glyphs {
+            for y in 0..height {
+                for byte in 0..bytes_per_row {
+                    let value = (glyph * 31 + y * 7 + byte * 3) as u8;
+                    let last = byte == bytes_per_row - 1;
+                    file.push(if last { value & padding_mask } else { value });
+                }
+            }
+        }
+
+        if with_table {
+            for glyph in 0..num_glyphs {
+                let codepoint = char::from_u32(0x20 + glyph * 97).unwrap_or('?');
+                file.extend(codepoint.to_string().as_bytes());
+
+                if glyph % 5 == 0 {
+                    file.push(PSF2_STARTSEQ);
+                    file.extend("e\u{301}".as_bytes());
+                }
+
+                file.push(PSF2_SEPARATOR);
+            }
+        }
+
+        file
+    }
+
+    fn round_trip(file: &[u8]) -> Vec<u8> {
+        Font::from_file(file).unwrap().construct_font_file()
+    }
+
+    #[test]
+    fn psf2_glyph_rows() {
+        let file = psf2_file(12, 3, false);
+        let font = Font::from_file(&file).unwrap();
+
+        assert_eq!((font.version, font.width, font.height), (2, 12, 16));
+        assert_eq!(font.glyphs.len(), 3);
+        assert!(font.unicode_table.is_none());
+
+        // Glyph 1, row 2 is 0x2d 0x30: ..#.##.# ..##
+        let row: String =
+            (0..12).map(|x| if font.glyphs[1].get(x, 2) { '#' } else { '.' }).collect();
+        assert_eq!(row, "..#.##.#..##");
+    }
+
+    #[test]
+    fn psf1_round_trip_all_modes() {
+        for mode in 0..8 {
+            let file = psf1_file(mode);
+
+            assert!(round_trip(&file) == file, "mode {mode:#x} does not round-trip");
+        }
+    }
+
+    #[test]
+    fn psf1_unicode_table_edits() {
+        let mut font = Font::from_file(&psf1_file(PSF1_MODEHASSEQ)).unwrap();
+        let entry = font.unicode_entry(3).unwrap();
+        assert_eq!(entry.codepoints, [3]);
+        assert_eq!(entry.sequences, [vec![0x41, 0x301], vec![0x45, 0x300]]);
+
+        font.unicode_entry_mut(3).codepoints.push(0x2500);
+        font.unicode_entry_mut(4).sequences.push(vec![0x6f, 0x308]);
+
+        let saved = Font::from_file(&font.construct_font_file()).unwrap();
+        assert_eq!(saved.version, 1);
+        assert_eq!(saved.unicode_entry(3).unwrap().codepoints, [3, 0x2500]);
+        assert_eq!(saved.unicode_entry(4).unwrap().sequences, [vec![0x6f, 0x308]]);
+
+        // A table gets created on the first edit, codepoints past UCS-2 need PSF2
+        let mut font = Font::from_file(&psf1_file(0)).unwrap();
+        font.unicode_entry_mut(0).codepoints.push(0x1f600);
+        assert_eq!(font.unicode_table.as_ref().unwrap().len(), 256);
+        assert_eq!(font.save_version(), 2);
+    }
+
+    #[test]
+    fn psf1_mode512_keeps_glyph_count() {
+        let file = psf1_file(PSF1_MODE512);
+        let saved = round_trip(&file);
+
+        assert_eq!(saved[2] & PSF1_MODE512, PSF1_MODE512);
+        assert_eq!(saved.len(), 4 + 512 * 14);
+    }
+
+    #[test]
+    fn psf2_round_trip() {
+        for (width, num_glyphs) in [(8, 256), (8, 512), (12, 300), (16, 100), (17, 1)] {
+            for with_table in [false, true] {
+                let file = psf2_file(width, num_glyphs, with_table);
+                let saved = round_trip(&file);
+
+                assert!(saved == file, "{width}px {num_glyphs} glyphs does not round-trip");
+            }
+        }
+    }
+
+    #[test]
+    fn psf2_unicode_sequences() {
+        let font = Font::from_file(&psf2_file(16, 6, true)).unwrap();
+        let entry = |idx| font.unicode_entry(idx).unwrap();
+
+        assert_eq!(entry(0).codepoints, [0x20]);
+        assert_eq!(entry(0).sequences, [vec![0x65, 0x301]]);
+        assert_eq!(entry(4).codepoints, [0x20 + 4 * 97]);
+        assert!(entry(4).sequences.is_empty());
+
+        // Multi-byte UTF-8 is written back as it was read
+        let mut font = Font::new(8, 8, 1);
+        font.unicode_entry_mut(0).codepoints.extend([0xe9, 0x2500, 0x1f600]);
+        font.unicode_entry_mut(0).sequences.push(vec![0x41, 0x30a]);
+
+        let file = font.construct_font_file();
+        let mut table = "é─😀".as_bytes().to_vec();
+        table.extend([PSF2_STARTSEQ, b'A', 0xcc, 0x8a, PSF2_SEPARATOR]);
+        assert_eq!(file[32 + 8..], table);
+        assert!(round_trip(&file) == file);
+    }
+
+    #[test]
+    fn psf2_is_picked_when_psf1_does_not_fit() {
+        let magic = |font: &Font| font.construct_font_file()[..2].to_vec();
+
+        assert_eq!(magic(&Font::new(8, 16, 256)), [PSF1_MAGIC0, PSF1_MAGIC1]);
+        assert_eq!(magic(&Font::new(12, 16, 256)), PSF2_MAGIC[..2]);
+        assert_eq!(magic(&Font::new(8, 16, 300)), PSF2_MAGIC[..2]);
+
+        let mut font = Font::new(8, 16, 256);
+        font.version = 2;
+        assert_eq!(magic(&font), PSF2_MAGIC[..2]);
+
+        // 12 pixel rows take two bytes, the last one padded on the right
+        let mut font = Font::new(12, 2, 1);
+        font.glyphs[0].set(0, 0);
+        font.glyphs[0].set(11, 1);
+        let file = font.construct_font_file();
+        assert_eq!(file[12..32], [0, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0, 12, 0, 0, 0]);
+        assert_eq!(file[32..], [0x80, 0x00, 0x00, 0x10]);
+    }
+
+    #[test]
+    fn psf2_header_round_trip() {
+        let header = Psf2Header {
+            version: 0,
+            headersize: 36,
+            flags: 0x80,
+            length: 2,
+            charsize: 20,
+            height: 8,
+            width: 12,
+        };
+
+        let mut file = header.serialize();
+        file.extend([0xde, 0xad, 0xbe, 0xef]);
+
+        for glyph in 0..2u8 {
+            let rows: Vec<u8> = (0..8u8).flat_map(|y| [glyph ^ y, 0xf0]).collect();
+            file.extend(rows);
+            file.extend([0; 4]);
+        }
+
+        assert!(round_trip(&file) == file);
+
+        // A resized font drops the padding that was sized for the old glyphs
+        let mut font = Font::from_file(&file).unwrap();
+        font.resize(12, 9, Edge::End, Edge::End);
+        assert_eq!(font.construct_font_file()[20..24], 18u32.to_le_bytes());
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let files = [psf1_file(PSF1_MODEHASSEQ), psf2_file(12, 40, true)];
+
+        for file in files {
+            for len in 0..file.len() {
+                assert!(Font::from_file(&file[..len]).is_err(), "{len} bytes parsed");
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt::{self, Write};
+
+use super::{write_string, BitMatrix, Font};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SourceOptions {
+    // Write the unicode table as a (codepoint, glyph) lookup table, if the font has one
+    pub unicode_lookup: bool,
+}
+
+// Comment and array syntax is the only thing that differs between the two languages
+struct Syntax {
+    comment: fn(&str) -> String,
+    glyph_start: &'static str,
+    glyph_end: &'static str,
+    row_indent: &'static str,
+}
+
+const C_SYNTAX: Syntax = Syntax {
+    comment: |text| format!("/* {text} */"),
+    glyph_start: "",
+    glyph_end: "",
+    row_indent: "    ",
+};
+
+const RUST_SYNTAX: Syntax = Syntax {
+    comment: |text| format!("// {text}"),
+    glyph_start: "    [\n",
+    glyph_end: "    ],\n",
+    row_indent: "        ",
+};
+
+impl Default for SourceOptions {
+    fn default() -> Self {
+        Self {
+            unicode_lookup: true,
+        }
+    }
+}
+
+impl Font {
+    // The unicode lookup table holds (codepoint, glyph) pairs sorted by codepoint and leaves
+    // out sequences
+    pub fn construct_c_header(&self, options: &SourceOptions) -> String {
+        write_string(|out| self.write_c_header(out, options))
+    }
+
+    pub fn construct_rust_source(&self, options: &SourceOptions) -> String {
+        write_string(|out| self.write_rust_source(out, options))
+    }
+
+    fn write_c_header(&self, out: &mut String, options: &SourceOptions) -> fmt::Result {
+        writeln!(out, "/* {} */\n", self.summary())?;
+        writeln!(out, "#ifndef FONT_H\n#define FONT_H\n")?;
+        writeln!(out, "#define FONT_WIDTH {}", self.width)?;
+        writeln!(out, "#define FONT_HEIGHT {}", self.height)?;
+        writeln!(out, "#define FONT_GLYPHS {}", self.glyphs.len())?;
+        writeln!(out, "#define FONT_GLYPH_SIZE {}\n", self.glyph_size())?;
+        writeln!(out, "static const unsigned char font[FONT_GLYPHS * FONT_GLYPH_SIZE] = {{")?;
+        self.write_glyphs(out, &C_SYNTAX)?;
+        writeln!(out, "}};")?;
+
+        if let Some(lookup) = self.unicode_lookup(options) {
+            writeln!(out, "\n#define FONT_UNICODE_ENTRIES {}\n", lookup.len())?;
+            writeln!(out, "static const struct {{")?;
+            writeln!(out, "    unsigned int codepoint;")?;
+            writeln!(out, "    unsigned int glyph;")?;
+            writeln!(out, "}} font_unicode[FONT_UNICODE_ENTRIES] = {{")?;
+
+            for (codepoint, idx) in lookup {
+                writeln!(out, "    {{ 0x{codepoint:04X}, {idx} }},")?;
+            }
+
+            writeln!(out, "}};")?;
+        }
+
+        writeln!(out, "\n#endif")
+    }
+
+    fn write_rust_source(&self, out: &mut String, options: &SourceOptions) -> fmt::Result {
+        writeln!(out, "// {}\n", self.summary())?;
+        writeln!(out, "pub const FONT_WIDTH: usize = {};", self.width)?;
+        writeln!(out, "pub const FONT_HEIGHT: usize = {};", self.height)?;
+        writeln!(out, "pub const FONT_GLYPHS: usize = {};", self.glyphs.len())?;
+        writeln!(out, "pub const FONT_GLYPH_SIZE: usize = {};\n", self.glyph_size())?;
+        writeln!(out, "pub static FONT: [[u8; FONT_GLYPH_SIZE]; FONT_GLYPHS] = [")?;
+        self.write_glyphs(out, &RUST_SYNTAX)?;
+        writeln!(out, "];")?;
+
+        if let Some(lookup) = self.unicode_lookup(options) {
+            writeln!(out, "\n// (codepoint, glyph) sorted by codepoint")?;
+            writeln!(out, "pub static FONT_UNICODE: [(u32, u32); {}] = [", lookup.len())?;
+
+            for (codepoint, idx) in lookup {
+                writeln!(out, "    (0x{codepoint:04X}, {idx}),")?;
+            }
+
+            writeln!(out, "];")?;
+        }
+
+        Ok(())
+    }
+
+    fn summary(&self) -> String {
+        let (width, height, count) = (self.width, self.height, self.glyphs.len());
+        format!("Generated by psfe: {width}x{height}, {count} glyphs")
+    }
+
+    fn glyph_size(&self) -> usize {
+        (self.width as usize).div_ceil(8) * self.height as usize
+    }
+
+    fn write_glyphs(&self, out: &mut String, syntax: &Syntax) -> fmt::Result {
+        let bytes_per_row = (self.width as usize).div_ceil(8);
+
+        for (idx, glyph) in self.glyphs.iter().enumerate() {
+            let mut title = idx.to_string();
+
+            for codepoint in self.unicode_entry(idx).into_iter().flat_map(|e| &e.codepoints) {
+                write!(title, " U+{codepoint:04X}")?;
+            }
+
+            writeln!(out, "    {}", (syntax.comment)(&title))?;
+            out.push_str(syntax.glyph_start);
+
+            for (y, row) in glyph.serialize().chunks(bytes_per_row).enumerate() {
+                let bytes: Vec<_> = row.iter().map(|byte| format!("0x{byte:02x},")).collect();
+                let art = (syntax.comment)(&ascii_art(glyph, y));
+
+                writeln!(out, "{}{} {art}", syntax.row_indent, bytes.join(" "))?;
+            }
+
+            out.push_str(syntax.glyph_end);
+        }
+
+        Ok(())
+    }
+
+    fn unicode_lookup(&self, options: &SourceOptions) -> Option<Vec<(u32, usize)>> {
+        let table = self.unicode_table.as_ref().filter(|_| options.unicode_lookup)?;
+        let mut lookup: Vec<_> = table
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, entry)| entry.codepoints.iter().map(move |&cp| (cp, idx)))
+            .collect();
+
+        // The first glyph mapped to a codepoint wins, like the console does
+        lookup.sort_by_key(|&(codepoint, _)| codepoint);
+        lookup.dedup_by_key(|&mut (codepoint, _)| codepoint);
+
+        Some(lookup)
+    }
+}
+
+fn ascii_art(glyph: &BitMatrix, y: usize) -> String {
+    (0..glyph.width as usize).map(|x| if glyph.get(x, y) { '#' } else { '.' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> Font {
+        let mut font = Font::new(8, 2, 2);
+        font.glyphs[1].set(0, 0);
+        font.glyphs[1].set(7, 1);
+        font.unicode_entry_mut(1).codepoints.extend([0x41, 0x391]);
+        font.unicode_entry_mut(0).codepoints.push(0x41);
+        font
+    }
+
+    #[test]
+    fn c_header() {
+        let header = font().construct_c_header(&SourceOptions::default());
+
+        assert!(header.contains("#define FONT_GLYPH_SIZE 2\n"));
+        assert!(header.contains("    /* 1 U+0041 U+0391 */\n    0x80, /* #....... */\n"));
+        assert!(header.contains("    0x01, /* .......# */\n};\n"));
+        assert!(header.contains("    { 0x0041, 0 },\n    { 0x0391, 1 },\n};\n"));
+
+        let options = SourceOptions {
+            unicode_lookup: false,
+        };
+        let header = font().construct_c_header(&options);
+        assert!(header.contains("    /* 1 U+0041 U+0391 */\n"));
+        assert!(!header.contains("font_unicode"));
+    }
+
+    #[test]
+    fn rust_source() {
+        let options = SourceOptions::default();
+        let source = font().construct_rust_source(&options);
+
+        assert!(source.contains("pub static FONT: [[u8; FONT_GLYPH_SIZE]; FONT_GLYPHS] = [\n"));
+        assert!(source.contains("    // 1 U+0041 U+0391\n    [\n        0x80, // #.......\n"));
+        assert!(source.contains("pub static FONT_UNICODE: [(u32, u32); 2] = [\n"));
+
+        let without_lookup = SourceOptions {
+            unicode_lookup: false,
+        };
+        assert!(!font().construct_rust_source(&without_lookup).contains("FONT_UNICODE"));
+
+        let mut font = font();
+        font.unicode_table = None;
+        assert!(!font.construct_rust_source(&options).contains("FONT_UNICODE"));
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use super::{write_string, Font, LineError, UnicodeEntry};
+
+const TEXT_MAGIC: &str = "psfe-text 1";
+
+// Glyphs that are left out still take up memory, a glyph per codepoint is the most any font
+// should need
+const MAX_GLYPHS: usize = 0x110000;
+
+// A header with `key value` lines is followed by one block per glyph:
+//
+//   glyph 65 U+0041
+//   sequence U+0041 U+030A
+//   note Latin capital A
+//   ..##....
+//   .#..#...
+//
+// Blank lines are ignored and glyphs that are left out stay empty
+impl Font {
+    pub fn is_text_font(file: &[u8]) -> bool {
+        file.starts_with(TEXT_MAGIC.as_bytes())
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, LineError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        match lines.next() {
+            Some((_, TEXT_MAGIC)) => (),
+            _ => return Err(LineError::new(1, &format!("expected \"{TEXT_MAGIC}\""))),
+        }
+
+        let mut width = None;
+        let mut height = None;
+        let mut num_glyphs = None;
+        let mut font_version = None;
+        let mut unicode = false;
+        let mut lines = lines.peekable();
+
+        while let Some(&(line, content)) = lines.peek() {
+            let (key, value) = content.split_once(' ').unwrap_or((content, ""));
+
+            match key {
+                "width" => width = Some(parse_number(value, line)?),
+                "height" => height = Some(parse_number(value, line)?),
+                "glyphs" => num_glyphs = Some(parse_number(value, line)?),
+                "version" => match parse_number(value, line)? {
+                    version @ (1 | 2) => font_version = Some(version),
+                    _ => return Err(LineError::new(line, "version must be 1 or 2")),
+                },
+                "unicode" => unicode = value.trim() == "yes",
+                "glyph" => break,
+                _ => return Err(LineError::new(line, &format!("unknown header field \"{key}\""))),
+            }
+
+            lines.next();
+        }
+
+        let (Some(width), Some(height), Some(num_glyphs)) = (width, height, num_glyphs) else {
+            return Err(LineError::new(1, "header needs width, height and glyphs"));
+        };
+
+        if width == 0 || height == 0 || !(1..=MAX_GLYPHS).contains(&num_glyphs) {
+            let message = format!("unsupported font of {num_glyphs} {width}x{height} glyphs");
+            return Err(LineError::new(1, &message));
+        }
+
+        let mut font = Font::new(width, height, num_glyphs);
+        font.version = font_version.unwrap_or(font.version);
+
+        if unicode {
+            font.unicode_table = Some(vec![UnicodeEntry::default(); num_glyphs]);
+        }
+
+        while let Some((line, content)) = lines.next() {
+            let mut words = content.split_whitespace();
+
+            if words.next() != Some("glyph") {
+                return Err(LineError::new(line, "expected \"glyph\""));
+            }
+
+            let idx: usize = match words.next().map(str::parse) {
+                Some(Ok(idx)) if idx < num_glyphs => idx,
+                _ => return Err(LineError::new(line, "bad glyph index")),
+            };
+
+            let codepoints =
+                words.map(|word| parse_codepoint(word, line)).collect::<Result<_, _>>()?;
+            let mut entry = UnicodeEntry {
+                codepoints,
+                sequences: vec![],
+            };
+            let mut notes: Vec<&str> = vec![];
+
+            while let Some(&(line, content)) = lines.peek() {
+                let (key, value) = content.split_once(' ').unwrap_or((content, ""));
+
+                match key {
+                    "sequence" => {
+                        let sequence =
+                            value.split_whitespace().map(|word| parse_codepoint(word, line));
+                        entry.sequences.push(sequence.collect::<Result<_, _>>()?);
+                    }
+                    "note" => notes.push(value),
+                    _ => break,
+                }
+
+                lines.next();
+            }
+
+            for y in 0..height as usize {
+                let Some((line, row)) = lines.next() else {
+                    return Err(LineError::new(line, "glyph is missing rows"));
+                };
+
+                if row.len() != width as usize {
+                    return Err(LineError::new(line, &format!("row must be {width} pixels wide")));
+                }
+
+                for (x, pixel) in row.chars().enumerate() {
+                    let set = match pixel {
+                        '#' => true,
+                        '.' => false,
+                        _ => return Err(LineError::new(line, "rows may only contain '#' and '.'")),
+                    };
+
+                    font.glyphs[idx].set_to(x, y, set);
+                }
+            }
+
+            if !notes.is_empty() {
+                font.notes.insert(idx, notes.join("\n"));
+            }
+
+            if !entry.codepoints.is_empty() || !entry.sequences.is_empty() {
+                *font.unicode_entry_mut(idx) = entry;
+            }
+        }
+
+        Ok(font)
+    }
+
+    pub fn construct_text_file(&self) -> String {
+        write_string(|out| self.write_text_file(out))
+    }
+
+    fn write_text_file(&self, out: &mut String) -> fmt::Result {
+        let unicode = if self.unicode_table.is_some() { "yes" } else { "no" };
+
+        writeln!(out, "{TEXT_MAGIC}")?;
+        writeln!(out, "width {}", self.width)?;
+        writeln!(out, "height {}", self.height)?;
+        writeln!(out, "glyphs {}", self.glyphs.len())?;
+        writeln!(out, "version {}", self.version)?;
+        writeln!(out, "unicode {unicode}")?;
+
+        for (idx, glyph) in self.glyphs.iter().enumerate() {
+            let entry = self.unicode_entry(idx);
+
+            write!(out, "\nglyph {idx}")?;
+
+            for codepoint in entry.iter().flat_map(|entry| &entry.codepoints) {
+                write!(out, " U+{codepoint:04X}")?;
+            }
+
+            writeln!(out)?;
+
+            for sequence in entry.iter().flat_map(|entry| &entry.sequences) {
+                write!(out, "sequence")?;
+
+                for codepoint in sequence {
+                    write!(out, " U+{codepoint:04X}")?;
+                }
+
+                writeln!(out)?;
+            }
+
+            for note in self.notes.get(&idx).iter().flat_map(|note| note.lines()) {
+                writeln!(out, "note {note}")?;
+            }
+
+            for y in 0..self.height as usize {
+                let row: String = (0..self.width as usize)
+                    .map(|x| if glyph.get(x, y) { '#' } else { '.' })
+                    .collect();
+
+                writeln!(out, "{row}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str, line: usize) -> Result<T, LineError> {
+    value.trim().parse().map_err(|_| LineError::new(line, &format!("bad number \"{value}\"")))
+}
+
+fn parse_codepoint(word: &str, line: usize) -> Result<u32, LineError> {
+    word.strip_prefix("U+")
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .filter(|&codepoint| codepoint <= 0x10ffff)
+        .ok_or_else(|| LineError::new(line, &format!("bad codepoint \"{word}\"")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "\
+psfe-text 1
+width 4
+height 3
+glyphs 3
+version 2
+unicode yes
+
+glyph 0
+....
+....
+....
+
+glyph 1
+....
+....
+....
+
+glyph 2 U+00C5 U+212B
+sequence U+0041 U+030A
+note A with ring above
+note also the angstrom sign
+.#..
+#.#.
+###.
+";
+
+    #[test]
+    fn round_trip() {
+        let font = Font::from_text(TEXT).unwrap();
+        let entry = font.unicode_entry(2).unwrap();
+
+        assert_eq!((font.width, font.height, font.glyphs.len()), (4, 3, 3));
+        assert_eq!(entry.codepoints, [0xc5, 0x212b]);
+        assert_eq!(entry.sequences, [vec![0x41, 0x30a]]);
+        assert_eq!(font.notes[&2], "A with ring above\nalso the angstrom sign");
+        assert!(font.glyphs[2].get(1, 0) && font.glyphs[2].get(0, 1) && !font.glyphs[2].get(1, 1));
+        assert_eq!(font.construct_text_file(), TEXT);
+
+        // Left out glyphs are empty
+        let sparse = TEXT.replace("glyph 0\n....\n....\n....\n", "");
+        assert!(Font::from_text(&sparse).unwrap().glyphs == font.glyphs);
+    }
+
+    #[test]
+    fn errors() {
+        let line = |text: &str| Font::from_text(text).err().unwrap().line;
+
+        assert_eq!(line("psfe-text 2\n"), 1);
+        assert_eq!(line(&TEXT.replace("#.#.", "#.#")), 23);
+        assert_eq!(line(&TEXT.replace("#.#.", "#x#.")), 23);
+        assert_eq!(line(&TEXT.replace("glyph 2", "glyph 3")), 18);
+        assert_eq!(line(&TEXT.replace("U+212B", "212B")), 18);
+        assert_eq!(line(&TEXT.replace("version 2", "colour red")), 5);
+        assert_eq!(line(&TEXT.replace("width 4", "width 0")), 1);
+        assert_eq!(line(&TEXT.replace("glyphs 3", "glyphs 0")), 1);
+        assert_eq!(line(&TEXT.replace("glyphs 3", "glyphs 4000000000")), 1);
+    }
+}
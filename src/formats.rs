@@ -0,0 +1,221 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use psfe::font::{BdfMapping, Font, KernelFontDesc, PsfHeader, SourceOptions};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// What a font file was read as, PSF files keep their header as it was
+#[derive(Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    Psf(PsfHeader),
+    Bdf,
+    Hex,
+    Text,
+    Kernel,
+    Gzip(Box<InputFormat>),
+}
+
+// Without a BDF mapping, ISO10646 fonts go into the unicode table and others into slots
+pub fn load_font(
+    path: &Path,
+    bdf_mapping: Option<BdfMapping>,
+) -> Result<(Font, InputFormat), String> {
+    let file = std::fs::read(path).map_err(|err| format!("failed to read file: {err}"))?;
+
+    decode_font(&file, bdf_mapping)
+}
+
+pub fn decode_font(
+    file: &[u8],
+    bdf_mapping: Option<BdfMapping>,
+) -> Result<(Font, InputFormat), String> {
+    if file.starts_with(&GZIP_MAGIC) {
+        let mut decompressed = vec![];
+
+        GzDecoder::new(file)
+            .read_to_end(&mut decompressed)
+            .map_err(|err| format!("failed to decompress file: {err}"))?;
+
+        let (font, format) = decode_font(&decompressed, bdf_mapping)?;
+        return Ok((font, InputFormat::Gzip(Box::new(format))));
+    }
+
+    if file.starts_with(b"STARTFONT") {
+        let text = String::from_utf8_lossy(file);
+        let font = match bdf_mapping {
+            Some(mapping) => Font::from_bdf_with_mapping(&text, mapping),
+            None => Font::from_bdf(&text),
+        };
+
+        let font = font.map_err(|err| format!("failed to parse BDF font: {err}"))?;
+        return Ok((font, InputFormat::Bdf));
+    }
+
+    if Font::is_text_font(file) {
+        let text = String::from_utf8_lossy(file);
+        let font =
+            Font::from_text(&text).map_err(|err| format!("failed to parse text font: {err}"))?;
+        return Ok((font, InputFormat::Text));
+    }
+
+    let text = String::from_utf8_lossy(file);
+
+    if text.contains("struct font_desc") {
+        let font = Font::from_kernel_source(&text)
+            .map_err(|err| format!("failed to parse kernel font: {err}"))?;
+        return Ok((font, InputFormat::Kernel));
+    }
+
+    if is_hex_line(text.lines().next().unwrap_or_default()) {
+        let font =
+            Font::from_hex(&text).map_err(|err| format!("failed to parse hex font: {err}"))?;
+        return Ok((font, InputFormat::Hex));
+    }
+
+    let parse_error = |err| format!("failed to parse font: {err}");
+    let header = PsfHeader::parse(file).map_err(parse_error)?;
+    let font = Font::from_file(file).map_err(parse_error)?;
+
+    Ok((font, InputFormat::Psf(header)))
+}
+
+// The output format is picked from the extension, a path without one is saved as PSF and an
+// unknown one is an error rather than PSF in disguise. A trailing .gz compresses whatever
+// the extension before it selects
+pub fn encode_font(font: &Font, path: &Path, source: &SourceOptions) -> Result<Vec<u8>, String> {
+    let extension = |path: &Path| {
+        path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_ascii_lowercase()
+    };
+
+    if extension(path) == "gz" {
+        let data = encode_font(font, &path.with_extension(""), source)?;
+        let mut encoder = GzEncoder::new(vec![], Compression::best());
+
+        // Writing into a Vec can't fail
+        encoder.write_all(&data).unwrap();
+        return Ok(encoder.finish().unwrap());
+    }
+
+    let file = match extension(path).as_str() {
+        "bdf" => font.construct_bdf_file().into_bytes(),
+        "h" => font.construct_c_header(source).into_bytes(),
+        "rs" => font.construct_rust_source(source).into_bytes(),
+        "c" => font.construct_kernel_source(&kernel_font_desc(path)).into_bytes(),
+        "hex" if !font.fits_hex() => {
+            let (width, height) = (font.width, font.height);
+            return Err(format!("hex files need 8x16 or 16x16 glyphs, not {width}x{height}"));
+        }
+        "hex" => font.construct_hex_file().into_bytes(),
+        "txt" => font.construct_text_file().into_bytes(),
+        "psf" | "psfu" | "" => font.construct_font_file(),
+        other => {
+            let supported = ".psf, .psfu, .bdf, .hex, .txt, .h, .rs and .c";
+            return Err(format!("unknown extension \".{other}\", supported are {supported}"));
+        }
+    };
+
+    Ok(file)
+}
+
+// Unifont lines look like 0041:0000000018242442427E424242420000
+fn is_hex_line(line: &str) -> bool {
+    line.split_once(':').is_some_and(|(codepoint, bitmap)| {
+        let is_hex = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit());
+        is_hex(codepoint) && is_hex(bitmap.trim_end())
+    })
+}
+
+// Overwriting a kernel font keeps its symbols, new files are named after the file, so
+// font_10x18.c becomes font_10x18 with the index FONT10x18_IDX
+fn kernel_font_desc(path: &Path) -> KernelFontDesc {
+    let existing = std::fs::read_to_string(path).ok();
+
+    existing.as_deref().and_then(KernelFontDesc::from_source).unwrap_or_else(|| {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        KernelFontDesc::from_name(stem.strip_prefix("font_").unwrap_or(&stem))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gzip_round_trip() {
+        let mut font = Font::new(8, 16, 256);
+        font.glyphs[65].set(3, 4);
+
+        for (name, format) in [
+            (
+                "font.psf.gz",
+                InputFormat::Psf(PsfHeader::Psf1 {
+                    mode: 0,
+                    charsize: 16,
+                }),
+            ),
+            ("font.bdf.gz", InputFormat::Bdf),
+            ("font.hex.gz", InputFormat::Hex),
+        ] {
+            let file = encode_font(&font, Path::new(name), &SourceOptions::default()).unwrap();
+            assert!(file.starts_with(&GZIP_MAGIC));
+
+            let (decoded, decoded_format) = decode_font(&file, None).unwrap();
+            assert!(decoded.glyphs == font.glyphs);
+            assert_eq!(decoded_format, InputFormat::Gzip(Box::new(format)));
+        }
+    }
+
+    #[test]
+    fn bdf_mapping() {
+        let mut font = Font::new(8, 8, 2);
+        font.glyphs[1].set(0, 0);
+        font.unicode_entry_mut(0).codepoints.push(0x41);
+        font.unicode_entry_mut(1).codepoints.push(0x42);
+        let file = font.construct_bdf_file().into_bytes();
+
+        let (unicode, _) = decode_font(&file, None).unwrap();
+        assert_eq!(unicode.glyphs.len(), 2);
+        assert_eq!(unicode.glyph_index(0x42), Some(1));
+
+        let (slots, _) = decode_font(&file, Some(BdfMapping::Slots)).unwrap();
+        assert_eq!(slots.glyphs.len(), 256);
+        assert!(slots.unicode_table.is_none() && slots.glyphs[0x42].get(0, 0));
+    }
+
+    #[test]
+    fn output_extensions() {
+        let font = Font::new(8, 16, 256);
+        let encode = |name: &str| encode_font(&font, Path::new(name), &SourceOptions::default());
+
+        for name in ["font", "font.psf", "FONT.PSFU", "font.psf.gz"] {
+            assert!(encode(name).is_ok(), "{name}");
+        }
+
+        for name in ["font.png", "font.bfd", "font.png.gz"] {
+            assert!(encode(name).unwrap_err().contains(".psf, .psfu, .bdf"), "{name}");
+        }
+    }
+
+    #[test]
+    fn gzip_keeps_directory() {
+        let dir = std::env::temp_dir().join(format!("psfe-formats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The kernel writer looks for an existing source next to the compressed target
+        let desc = KernelFontDesc::from_name("Custom");
+        let font = Font::new(8, 8, 256);
+        std::fs::write(dir.join("font_x.c"), font.construct_kernel_source(&desc)).unwrap();
+
+        let options = SourceOptions::default();
+        let file = encode_font(&font, &dir.join("font_x.c.gz"), &options).unwrap();
+        let mut source = String::new();
+        GzDecoder::new(&file[..]).read_to_string(&mut source).unwrap();
+        assert!(source.contains("const struct font_desc font_custom = {"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,30 @@
+// 3x5 hex digits, one bit per pixel, rows top to bottom
+const DIGITS: [u16; 16] = [
+    0b111_101_101_101_111,
+    0b010_110_010_010_111,
+    0b111_001_111_100_111,
+    0b111_001_111_001_111,
+    0b101_101_111_001_001,
+    0b111_100_111_001_111,
+    0b111_100_111_101_111,
+    0b111_001_001_001_001,
+    0b111_101_111_101_111,
+    0b111_101_111_001_111,
+    0b010_101_111_101_101,
+    0b110_101_110_101_110,
+    0b011_100_100_100_011,
+    0b110_101_101_101_110,
+    0b111_100_111_100_111,
+    0b111_100_111_100_100,
+];
+
+pub const DIGIT_WIDTH: u32 = 3;
+pub const DIGIT_HEIGHT: u32 = 5;
+const DIGIT_ADVANCE: u32 = DIGIT_WIDTH + 1;
+
+// Exported images are sized by user options, this keeps them to 256 MiB of pixels
+const MAX_IMAGE_PIXELS: u64 = 1 << 26;
+
 pub struct Framebuffer {
     pub width: u32,
     pub height: u32,
@@ -18,10 +45,41 @@ impl Framebuffer {
         }
     }
 
+    pub fn try_new(width: u64, height: u64) -> Result<Self, String> {
+        let too_large = || format!("a {width}x{height} image is too large");
+
+        if width.checked_mul(height).is_none_or(|pixels| pixels > MAX_IMAGE_PIXELS) {
+            return Err(too_large());
+        }
+
+        // A side can only be that long when the other one is 0
+        let (Ok(w), Ok(h)) = (u32::try_from(width), u32::try_from(height)) else {
+            return Err(too_large());
+        };
+
+        Ok(Self::new(w, h))
+    }
+
     pub fn clear(&mut self) {
         self.pixels.fill(0);
     }
 
+    // Nearest-neighbour upscale for exported images
+    pub fn scaled(&self, factor: u32) -> Result<Self, String> {
+        let factor64 = u64::from(factor);
+        let mut scaled =
+            Self::try_new(u64::from(self.width) * factor64, u64::from(self.height) * factor64)?;
+
+        for y in 0..scaled.height {
+            for x in 0..scaled.width {
+                let src = (y / factor) * self.width + x / factor;
+                scaled.pixels[(y * scaled.width + x) as usize] = self.pixels[src as usize];
+            }
+        }
+
+        Ok(scaled)
+    }
+
     pub fn draw_pixel(&mut self, x: u32, y: u32, color: u32) {
         if x >= self.width || y >= self.height {
             return;
@@ -58,4 +116,38 @@ impl Framebuffer {
     pub fn draw_square_hollow(&mut self, x: u32, y: u32, size: u32, color: u32) {
         self.draw_rect_hollow(x, y, size, size, color);
     }
+
+    pub fn draw_number(&mut self, x: u32, y: u32, number: usize, color: u32) {
+        self.draw_digits(x, y, &number.to_string(), color);
+    }
+
+    // Codepoints are drawn like U+XXXX without the prefix
+    pub fn draw_codepoint(&mut self, x: u32, y: u32, codepoint: u32, color: u32) {
+        self.draw_digits(x, y, &format!("{codepoint:04X}"), color);
+    }
+
+    fn draw_digits(&mut self, x: u32, y: u32, digits: &str, color: u32) {
+        for (i, digit) in digits.chars().enumerate() {
+            let bits = DIGITS[digit.to_digit(16).unwrap() as usize];
+            let digit_x = x + i as u32 * DIGIT_ADVANCE;
+
+            for dy in 0..DIGIT_HEIGHT {
+                for dx in 0..DIGIT_WIDTH {
+                    let shift = (DIGIT_HEIGHT - dy) * DIGIT_WIDTH - dx - 1;
+
+                    if bits & (1 << shift) != 0 {
+                        self.draw_pixel(digit_x + dx, y + dy, color);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn number_width(number: usize) -> u32 {
+        number.to_string().len() as u32 * DIGIT_ADVANCE
+    }
+
+    pub fn codepoint_width(codepoint: u32) -> u32 {
+        format!("{codepoint:04X}").len() as u32 * DIGIT_ADVANCE
+    }
 }
@@ -0,0 +1,24 @@
+// Glyph cells laid out left to right, top to bottom, shared by the editor's glyph grid and
+// exported sheets
+pub struct GridLayout {
+    pub x: u32,
+    pub y: u32,
+    pub cell_w: u32,
+    pub cell_h: u32,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl GridLayout {
+    // Top-left corner of the cell at `slot`, counted from the first visible cell
+    pub fn cell_position(&self, slot: usize) -> (u32, u32) {
+        let column = (slot % self.columns) as u32;
+        let row = (slot / self.columns) as u32;
+
+        (self.x + column * self.cell_w, self.y + row * self.cell_h)
+    }
+
+    pub fn total_rows(&self, num_glyphs: usize) -> usize {
+        num_glyphs.div_ceil(self.columns)
+    }
+}
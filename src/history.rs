@@ -0,0 +1,145 @@
+use psfe::font::{BitMatrix, Font, UnicodeEntry};
+
+const MAX_HISTORY: usize = 256;
+
+// Font snapshots are only for edits that change its size or replace it, the rest store just
+// what they touch
+pub enum Change {
+    Glyph(usize, BitMatrix),
+    // Consecutive glyphs from the first index on
+    Glyphs(usize, Vec<BitMatrix>),
+    // None stands for a font without a unicode table
+    Unicode(usize, Option<UnicodeEntry>),
+    Font(Font),
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl History {
+    // Changes hold the state from before the edit was made
+    pub fn record(&mut self, change: Change) {
+        if self.undo.len() == MAX_HISTORY {
+            self.undo.remove(0);
+        }
+
+        self.undo.push(change);
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, font: &mut Font) -> bool {
+        let Some(change) = self.undo.pop() else {
+            return false;
+        };
+
+        self.redo.push(change.apply(font));
+
+        true
+    }
+
+    pub fn redo(&mut self, font: &mut Font) -> bool {
+        let Some(change) = self.redo.pop() else {
+            return false;
+        };
+
+        self.undo.push(change.apply(font));
+
+        true
+    }
+}
+
+impl Change {
+    // Puts the stored state back into the font and returns the change that reverts it
+    fn apply(self, font: &mut Font) -> Self {
+        match self {
+            Self::Glyph(idx, glyph) => {
+                let current = std::mem::replace(&mut font.glyphs[idx], glyph);
+                Self::Glyph(idx, current)
+            }
+            Self::Glyphs(start, glyphs) => {
+                let range = start..start + glyphs.len();
+                Self::Glyphs(start, font.glyphs.splice(range, glyphs).collect())
+            }
+            Self::Unicode(idx, entry) => {
+                let current = font.unicode_entry(idx).cloned();
+
+                match entry {
+                    Some(entry) => *font.unicode_entry_mut(idx) = entry,
+                    None => font.unicode_table = None,
+                }
+
+                Self::Unicode(idx, current)
+            }
+            Self::Font(other) => Self::Font(std::mem::replace(font, other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo_glyph() {
+        let mut font = Font::new(8, 8, 256);
+        let mut history = History::default();
+
+        history.record(Change::Glyph(65, font.glyphs[65].clone()));
+        font.glyphs[65].set(3, 4);
+
+        assert!(history.undo(&mut font));
+        assert!(!font.glyphs[65].get(3, 4));
+        assert!(!history.undo(&mut font));
+
+        assert!(history.redo(&mut font));
+        assert!(font.glyphs[65].get(3, 4));
+        assert!(!history.redo(&mut font));
+    }
+
+    #[test]
+    fn record_clears_redo() {
+        let mut font = Font::new(8, 8, 256);
+        let mut history = History::default();
+
+        history.record(Change::Font(font.clone()));
+        font.decrease_height();
+        history.undo(&mut font);
+        assert_eq!(font.height, 8);
+
+        history.record(Change::Glyph(0, font.glyphs[0].clone()));
+        assert!(!history.redo(&mut font));
+    }
+
+    #[test]
+    fn small_changes() {
+        let mut font = Font::new(8, 8, 256);
+        let mut history = History::default();
+
+        history.record(Change::Glyphs(128, font.glyphs[128..130].to_vec()));
+        font.glyphs[129].set(1, 1);
+
+        history.record(Change::Unicode(65, font.unicode_entry(65).cloned()));
+        font.unicode_entry_mut(65).codepoints.push(0x41);
+
+        history.record(Change::Unicode(65, font.unicode_entry(65).cloned()));
+        font.unicode_entry_mut(65).codepoints.push(0x391);
+
+        history.undo(&mut font);
+        assert_eq!(font.unicode_entry(65).unwrap().codepoints, [0x41]);
+
+        // The table didn't exist before the first codepoint was added
+        history.undo(&mut font);
+        assert!(font.unicode_table.is_none());
+
+        history.undo(&mut font);
+        assert!(!font.glyphs[129].get(1, 1));
+
+        history.redo(&mut font);
+        history.redo(&mut font);
+        assert!(font.glyphs[129].get(1, 1));
+        assert_eq!(font.unicode_entry(65).unwrap().codepoints, [0x41]);
+    }
+}
@@ -0,0 +1,3 @@
+#![allow(clippy::unreadable_literal)]
+
+pub mod font;
@@ -1,20 +1,115 @@
 #![allow(clippy::unreadable_literal)]
 
+mod args;
+mod blank;
+mod cli;
+mod formats;
 mod framebuffer;
+mod grid;
+mod history;
 mod rendering_backend;
+mod save;
+mod sheet;
 mod state;
+mod tools;
 
+use std::path::Path;
+
+use args::{Args, Command};
 use rendering_backend::{ChosenBackend, RenderingBackend};
 use state::State;
 
 fn main() {
-    let filename = "font.psf";
-    let file = std::fs::read(filename).expect("failed to read file");
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("psfe: {err}\n\n{}", args::USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    if args.help {
+        println!("{}", args::USAGE);
+        return;
+    }
+
+    let (mut font, format) = if args.new_font {
+        (args.blank.build(), None)
+    } else {
+        let path = args.font_path.as_ref().unwrap();
+        let (font, format) =
+            formats::load_font(path, args.bdf_mapping).unwrap_or_else(|err| fail(path, &err));
+        (font, Some(format))
+    };
+
+    if let Some(path) = &args.import_sheet_path {
+        let imported = std::fs::read(path)
+            .map_err(|err| format!("failed to read file: {err}"))
+            .and_then(|png| sheet::import_sheet(&mut font, &png, &args.import_sheet));
+
+        if let Err(err) = imported {
+            fail(path, &err);
+        }
+    }
+
+    // Commands other than editing never open a window
+    match &args.command {
+        Command::Edit => (),
+        Command::Info => {
+            print!("{}", cli::info(&font, format.as_ref()));
+            return;
+        }
+        Command::Convert => {
+            let path = args.output_path.as_ref().unwrap();
+            let written = formats::encode_font(&font, path, &args.source).and_then(|file| {
+                save::write_atomic(path, &file, args.backup).map_err(|err| err.to_string())
+            });
+
+            if let Err(err) = written {
+                fail(path, &format!("failed to convert: {err}"));
+            }
+
+            return;
+        }
+        Command::Render(text) => {
+            let path = args.output_path.as_ref().unwrap();
+            let written = cli::render_text(&font, text, &args.sheet).and_then(|fb| {
+                let png = sheet::encode_png(&fb);
+                save::write_atomic(path, &png, args.backup).map_err(|err| err.to_string())
+            });
 
-    let w = 1024;
-    let h = 768;
+            if let Err(err) = written {
+                fail(path, &format!("failed to write image: {err}"));
+            }
+
+            return;
+        }
+    }
+
+    if let Some(path) = &args.sheet_path {
+        let written = sheet::render_sheet(&font, &args.sheet).and_then(|fb| {
+            let png = sheet::encode_png(&fb);
+            save::write_atomic(path, &png, args.backup).map_err(|err| err.to_string())
+        });
+
+        if let Err(err) = written {
+            fail(path, &format!("failed to write sheet: {err}"));
+        }
+
+        return;
+    }
+
+    let output_path = args.output_path.or(args.font_path);
+
+    let w = args.window_width;
+    let h = args.window_height;
     let mut backend = ChosenBackend::new(w, h, "psfe");
-    let state = State::new(w, h, &file);
+    let state = State::new(w, h, font, output_path, args.backup, args.source);
 
     backend.main_loop(state);
 }
+
+fn fail(path: &Path, err: &str) -> ! {
+    eprintln!("psfe: {}: {err}", path.display());
+    std::process::exit(1);
+}
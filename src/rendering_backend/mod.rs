@@ -13,13 +13,22 @@ pub enum Event {
     MousePress(MouseButton, i32, i32),
     MouseRelease(i32, i32),
     MouseWheel(i32),
+    TextInput(String),
 }
 
 #[derive(PartialEq, Eq)]
 pub enum KeyButton {
+    Backspace,
     Character(char),
+    Down,
     Escape,
+    Left,
     Minus,
+    PageDown,
+    PageUp,
+    Return,
+    Right,
+    Up,
 }
 
 #[derive(PartialEq, Eq)]
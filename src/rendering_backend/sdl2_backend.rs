@@ -55,6 +55,11 @@ impl Sdl2Backend {
                             State::events(state, event);
                         }
                     }
+                    SDL_EventType_SDL_TEXTINPUT => {
+                        let text = CStr::from_ptr(ret_event.text.text.as_ptr());
+                        let event = Event::TextInput(text.to_string_lossy().into_owned());
+                        State::events(state, event);
+                    }
                     SDL_EventType_SDL_MOUSEMOTION => {
                         let x = ret_event.motion.x;
                         let y = ret_event.motion.y;
@@ -91,6 +96,24 @@ impl Sdl2Backend {
         match keycode as u32 {
             SDL_KeyCode_SDLK_ESCAPE => Some(KeyButton::Escape),
             SDL_KeyCode_SDLK_MINUS => Some(KeyButton::Minus),
+            SDL_KeyCode_SDLK_PAGEDOWN => Some(KeyButton::PageDown),
+            SDL_KeyCode_SDLK_PAGEUP => Some(KeyButton::PageUp),
+            SDL_KeyCode_SDLK_RETURN => Some(KeyButton::Return),
+            SDL_KeyCode_SDLK_BACKSPACE => Some(KeyButton::Backspace),
+            SDL_KeyCode_SDLK_UP => Some(KeyButton::Up),
+            SDL_KeyCode_SDLK_DOWN => Some(KeyButton::Down),
+            SDL_KeyCode_SDLK_LEFT => Some(KeyButton::Left),
+            SDL_KeyCode_SDLK_RIGHT => Some(KeyButton::Right),
+            SDL_KeyCode_SDLK_0 => Some(KeyButton::Character('0')),
+            SDL_KeyCode_SDLK_1 => Some(KeyButton::Character('1')),
+            SDL_KeyCode_SDLK_2 => Some(KeyButton::Character('2')),
+            SDL_KeyCode_SDLK_3 => Some(KeyButton::Character('3')),
+            SDL_KeyCode_SDLK_4 => Some(KeyButton::Character('4')),
+            SDL_KeyCode_SDLK_5 => Some(KeyButton::Character('5')),
+            SDL_KeyCode_SDLK_6 => Some(KeyButton::Character('6')),
+            SDL_KeyCode_SDLK_7 => Some(KeyButton::Character('7')),
+            SDL_KeyCode_SDLK_8 => Some(KeyButton::Character('8')),
+            SDL_KeyCode_SDLK_9 => Some(KeyButton::Character('9')),
             SDL_KeyCode_SDLK_a => Some(KeyButton::Character('a')),
             SDL_KeyCode_SDLK_b => Some(KeyButton::Character('b')),
             SDL_KeyCode_SDLK_c => Some(KeyButton::Character('c')),
@@ -0,0 +1,121 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Writes to a temporary file next to the target and renames it over the target, so an
+// interrupted save never leaves a half-written font behind
+pub fn write_atomic(path: &Path, data: &[u8], backup: bool) -> io::Result<()> {
+    // Renaming onto a symlink would replace the link, so the file it points to is replaced
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let permissions = fs::metadata(&path).map(|metadata| metadata.permissions()).ok();
+    let (mut tmp, tmp_path) = create_tmp(&path)?;
+
+    let written = permissions
+        .map_or(Ok(()), |permissions| tmp.set_permissions(permissions))
+        .and_then(|()| tmp.write_all(data))
+        .and_then(|()| tmp.sync_all())
+        .and_then(|()| replace(&path, &tmp_path, backup));
+
+    // Whichever step failed, the temporary file is of no use anymore
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    written
+}
+
+// The name is unique to the process and never replaces a file that is already there
+fn create_tmp(path: &Path) -> io::Result<(File, PathBuf)> {
+    let mut attempt = 0;
+
+    loop {
+        let tmp_path = with_suffix(path, &format!(".{}-{attempt}.tmp", std::process::id()));
+
+        match OpenOptions::new().write(true).create_new(true).open(&tmp_path) {
+            Ok(file) => return Ok((file, tmp_path)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn replace(path: &Path, tmp_path: &Path, backup: bool) -> io::Result<()> {
+    if backup && path.exists() {
+        fs::copy(path, with_suffix(path, ".bak"))?;
+    }
+
+    fs::rename(tmp_path, path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    name.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn overwrite_with_backup() {
+        let dir = std::env::temp_dir().join(format!("psfe-save-{}", std::process::id()));
+        let path = dir.join("font.psf");
+        fs::create_dir_all(&dir).unwrap();
+
+        write_atomic(&path, b"old", true).unwrap();
+        assert_eq!(entries(&dir), ["font.psf"]);
+
+        // A file that happens to have a temporary name is left alone
+        fs::write(with_suffix(&path, ".tmp"), b"mine").unwrap();
+        write_atomic(&path, b"new", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(with_suffix(&path, ".bak")).unwrap(), b"old");
+        assert_eq!(fs::read(with_suffix(&path, ".tmp")).unwrap(), b"mine");
+        assert_eq!(entries(&dir), ["font.psf", "font.psf.bak", "font.psf.tmp"]);
+
+        // A directory can't be replaced by a file
+        let nested = dir.join("nested");
+        fs::create_dir_all(nested.join("font.psf")).unwrap();
+        assert!(write_atomic(&nested, b"new", false).is_err());
+        assert_eq!(entries(&dir), ["font.psf", "font.psf.bak", "font.psf.tmp", "nested"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_links_and_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("psfe-save-link-{}", std::process::id()));
+        let (target, link) = (dir.join("target.psf"), dir.join("link.psf"));
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(&target, b"old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"new", false).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
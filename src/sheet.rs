@@ -0,0 +1,319 @@
+use psfe::font::Font;
+
+use super::framebuffer::{Framebuffer, DIGIT_HEIGHT};
+use super::grid::GridLayout;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SheetLabels {
+    None,
+    Index,
+    Codepoint,
+}
+
+// Spacing is in font pixels, the whole sheet including labels is scaled afterwards
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SheetOptions {
+    pub columns: usize,
+    pub scale: u32,
+    pub spacing: u32,
+    pub foreground: u32,
+    pub background: u32,
+    pub labels: SheetLabels,
+}
+
+// Cells default to the font's glyph size, padding is the gap between neighbouring cells
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ImportOptions {
+    pub cell_size: Option<(u32, u32)>,
+    pub columns: usize,
+    pub origin: (u32, u32),
+    pub padding: u32,
+    pub threshold: u8,
+    pub invert: bool,
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        Self {
+            columns: 16,
+            scale: 1,
+            spacing: 2,
+            foreground: 0xffffff,
+            background: 0x000000,
+            labels: SheetLabels::None,
+        }
+    }
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: None,
+            columns: 16,
+            origin: (0, 0),
+            padding: 0,
+            threshold: 128,
+            invert: false,
+        }
+    }
+}
+
+pub fn render_sheet(font: &Font, options: &SheetOptions) -> Result<Framebuffer, String> {
+    let fw = font.width as u32;
+    let fh = font.height as u32;
+    let num_glyphs = font.glyphs.len();
+
+    let (label_w, label_h) = match options.labels {
+        SheetLabels::None => (0, 0),
+        SheetLabels::Index => {
+            (Framebuffer::number_width(num_glyphs.saturating_sub(1)), DIGIT_HEIGHT + 1)
+        }
+        SheetLabels::Codepoint => {
+            let widest = (0..num_glyphs)
+                .filter_map(|idx| glyph_codepoint(font, idx))
+                .map(Framebuffer::codepoint_width)
+                .max()
+                .unwrap_or(0);
+
+            (widest, DIGIT_HEIGHT + 1)
+        }
+    };
+
+    // The spacing comes straight from the options, so the size is checked before anything
+    // is laid out with it
+    let columns = options.columns.clamp(1, num_glyphs.max(1));
+    let rows = num_glyphs.div_ceil(columns).max(1);
+    let spacing = u64::from(options.spacing);
+    let cell_w = u64::from(fw.max(label_w)) + spacing;
+    let cell_h = u64::from(fh + label_h) + spacing;
+    let mut fb =
+        Framebuffer::try_new(spacing + columns as u64 * cell_w, spacing + rows as u64 * cell_h)?;
+    let (width, height) = (fb.width, fb.height);
+
+    let layout = GridLayout {
+        x: options.spacing,
+        y: options.spacing,
+        cell_w: cell_w as u32,
+        cell_h: cell_h as u32,
+        columns,
+        rows,
+    };
+
+    fb.draw_rect(0, 0, width, height, options.background);
+
+    for (idx, glyph) in font.glyphs.iter().enumerate() {
+        let (offset_x, offset_y) = layout.cell_position(idx);
+
+        for y in 0..fh {
+            for x in 0..fw {
+                if glyph.get(x as usize, y as usize) {
+                    fb.draw_pixel(offset_x + x, offset_y + y, options.foreground);
+                }
+            }
+        }
+
+        let label_y = offset_y + fh + 1;
+
+        match options.labels {
+            SheetLabels::None => (),
+            SheetLabels::Index => fb.draw_number(offset_x, label_y, idx, options.foreground),
+            SheetLabels::Codepoint => {
+                if let Some(codepoint) = glyph_codepoint(font, idx) {
+                    fb.draw_codepoint(offset_x, label_y, codepoint, options.foreground);
+                }
+            }
+        }
+    }
+
+    fb.scaled(options.scale.max(1))
+}
+
+pub fn encode_png(fb: &Framebuffer) -> Vec<u8> {
+    let mut file = vec![];
+    let mut encoder = png::Encoder::new(&mut file, fb.width, fb.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = fb
+        .pixels
+        .iter()
+        .flat_map(|pixel| {
+            let [r, g, b, _] = pixel.to_be_bytes();
+            [r, g, b]
+        })
+        .collect();
+
+    // Encoding into a Vec can only fail on bad dimensions, and sheets are at least 1x1
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&data).unwrap();
+    writer.finish().unwrap();
+
+    file
+}
+
+// Fills glyphs in order from the cells that fit completely inside the image and returns how
+// many were replaced
+pub fn import_sheet(font: &mut Font, png: &[u8], options: &ImportOptions) -> Result<usize, String> {
+    let (width, height, pixels) = threshold_png(png, options)?;
+    let fw = font.width as u32;
+    let fh = font.height as u32;
+    let (cell_w, cell_h) = options.cell_size.unwrap_or((fw, fh));
+
+    let layout = GridLayout {
+        x: options.origin.0,
+        y: options.origin.1,
+        cell_w: cell_w + options.padding,
+        cell_h: cell_h + options.padding,
+        columns: options.columns.max(1),
+        rows: 0,
+    };
+    let mut imported = 0;
+
+    for (idx, glyph) in font.glyphs.iter_mut().enumerate() {
+        let (offset_x, offset_y) = layout.cell_position(idx);
+
+        if offset_y + cell_h > height {
+            break;
+        }
+
+        if offset_x + cell_w > width {
+            continue;
+        }
+
+        glyph.clear_all();
+
+        for y in 0..cell_h.min(fh) {
+            for x in 0..cell_w.min(fw) {
+                let pixel = (offset_y + y) * width + offset_x + x;
+                glyph.set_to(x as usize, y as usize, pixels[pixel as usize]);
+            }
+        }
+
+        imported += 1;
+    }
+
+    if imported == 0 {
+        return Err(format!("no complete {cell_w}x{cell_h} cells in a {width}x{height} image"));
+    }
+
+    Ok(imported)
+}
+
+// Reduces the image to one bit per pixel, transparent pixels are always unset
+fn threshold_png(png: &[u8], options: &ImportOptions) -> Result<(u32, u32, Vec<bool>), String> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let decode_error = |err: png::DecodingError| format!("failed to decode PNG: {err}");
+    let mut reader = decoder.read_info().map_err(decode_error)?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(decode_error)?;
+
+    let pixels = data[..info.buffer_size()]
+        .chunks(info.color_type.samples())
+        .map(|pixel| {
+            let (r, g, b, a) = match *pixel {
+                [l] => (l, l, l, 255),
+                [l, a] => (l, l, l, a),
+                [r, g, b] => (r, g, b, 255),
+                [r, g, b, a] => (r, g, b, a),
+                _ => unreachable!(),
+            };
+            let luminance = (299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b)) / 1000;
+
+            a >= 128 && (luminance >= u32::from(options.threshold)) != options.invert
+        })
+        .collect();
+
+    Ok((info.width, info.height, pixels))
+}
+
+// Without a unicode table the glyph index is the codepoint, like the console assumes
+fn glyph_codepoint(font: &Font, idx: usize) -> Option<u32> {
+    match &font.unicode_table {
+        Some(_) => font.unicode_entry(idx)?.codepoints.first().copied(),
+        None => Some(idx as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheet_layout() {
+        let mut font = Font::new(8, 8, 20);
+        font.glyphs[17].set(0, 0);
+
+        let options = SheetOptions {
+            columns: 4,
+            scale: 2,
+            spacing: 1,
+            labels: SheetLabels::Index,
+            ..SheetOptions::default()
+        };
+        let fb = render_sheet(&font, &options).unwrap();
+
+        // 4 columns of 8 + 1, 5 rows of 8 + 6 + 1, plus the outer spacing, all doubled
+        assert_eq!((fb.width, fb.height), (74, 152));
+
+        // Glyph 17 is the second one in the fifth row
+        let (x, y) = ((1 + 9) * 2, (1 + 4 * 15) * 2);
+        assert_eq!(fb.pixels[(y * fb.width + x) as usize], 0xffffffff);
+        assert_eq!(fb.pixels[(y * fb.width + x + 2) as usize], 0x000000ff);
+
+        let png = encode_png(&fb);
+        assert!(png.starts_with(b"\x89PNG"));
+
+        // Sizes that don't fit in memory are errors instead of overflows
+        for (scale, spacing) in [(70000, 1), (1, u32::MAX), (3000, 1)] {
+            let options = SheetOptions {
+                scale,
+                spacing,
+                ..options
+            };
+            assert!(render_sheet(&font, &options).is_err());
+        }
+    }
+
+    #[test]
+    fn import_exported_sheet() {
+        let mut font = Font::new(8, 8, 20);
+        font.glyphs[0].set(7, 7);
+        font.glyphs[17].set(0, 0);
+        font.glyphs[19].set(3, 5);
+
+        let export = SheetOptions {
+            columns: 6,
+            spacing: 3,
+            foreground: 0x202020,
+            background: 0xf0f0f0,
+            ..SheetOptions::default()
+        };
+        let png = encode_png(&render_sheet(&font, &export).unwrap());
+
+        let import = ImportOptions {
+            columns: 6,
+            origin: (3, 3),
+            padding: 3,
+            invert: true,
+            ..ImportOptions::default()
+        };
+        let mut imported = Font::new(8, 8, 30);
+        imported.glyphs[23].set(1, 1);
+        imported.glyphs[29].set(1, 1);
+
+        // The last row has room for 4 more empty cells, the rest is outside the image
+        assert_eq!(import_sheet(&mut imported, &png, &import), Ok(24));
+        assert!(imported.glyphs[..20] == font.glyphs[..]);
+        assert!(!imported.glyphs[23].get(1, 1));
+        assert!(imported.glyphs[29].get(1, 1));
+
+        let cell_size = Some((100, 100));
+        let too_big = ImportOptions {
+            cell_size,
+            ..import
+        };
+        assert!(import_sheet(&mut imported, &png, &too_big).is_err());
+    }
+}
@@ -1,23 +1,30 @@
-use super::framebuffer::Framebuffer;
+use super::blank::BlankFont;
+use super::framebuffer::{Framebuffer, DIGIT_HEIGHT};
+use super::grid::GridLayout;
+use super::history::{Change, History};
 use super::rendering_backend::{Event, KeyButton, MouseButton};
+use super::tools::{self, Tool};
+use super::{formats, save};
+use psfe::font::{BitMatrix, Edge, Font, SourceOptions};
+use std::path::PathBuf;
 
 const GRID_OFFS_X: u32 = 3;
 const GRID_OFFS_Y: u32 = 3;
+const GRID_MAX_COLUMNS: u32 = 16;
+const GRID_MARGIN: u32 = 3;
+const GRID_SCROLLBAR_WIDTH: u32 = 4;
 
 const EDITOR_CELL_SIZE: u32 = 16;
 
-const PSF1_MAGIC0: u8 = 0x36;
-const PSF1_MAGIC1: u8 = 0x04;
-const PSF1_MODE512: u8 = 0x01;
-
 pub struct State {
     pub message_queue: Vec<Message>,
     pub fb: Framebuffer,
     font: Font,
 
-    glyph_hov: (u32, u32),
-    glyph_sel: (u32, u32),
+    glyph_hov: usize,
+    glyph_sel: usize,
     inside_glyphs_area: bool,
+    grid_scroll: usize,
 
     editor_hov: (i32, i32),
     inside_editor_area: bool,
@@ -27,48 +34,81 @@ pub struct State {
 
     drawing: bool,
     drawing_sets_bits_to: bool,
-
-    saves_counter: u32,
-}
-
-pub enum Message {
-    Quit,
-    ChangeWindowTitle(String),
+    stroke_start: Option<(usize, BitMatrix)>,
+    stroke_last: Option<(i32, i32)>,
+    tool: Tool,
+    shape_start: Option<(i32, i32)>,
+    shift_wraps: bool,
+
+    history: History,
+
+    unicode_input: Option<String>,
+
+    output_path: Option<PathBuf>,
+    path_input: Option<String>,
+    new_font_input: Option<String>,
+    resize: Option<Resize>,
+    prompt_key: Option<char>,
+    make_backups: bool,
+    source_options: SourceOptions,
+    status: Option<String>,
 }
 
-struct Font {
-    _version: u8,
+// A canvas size that is previewed in the editor until it's applied to the whole font
+#[derive(Clone, Copy)]
+struct Resize {
     width: u8,
     height: u8,
-    glyphs: Vec<BitMatrix>,
+    columns_at: Edge,
+    rows_at: Edge,
 }
 
-struct BitMatrix {
-    // This is a pretty wasteful structure, but it simplifies things
-    width: u8,
-    height: u8,
-    data: Vec<bool>,
+pub enum Message {
+    Quit,
+    ChangeWindowTitle(String),
 }
 
 impl State {
-    pub fn new(fb_width: u32, fb_height: u32, file: &[u8]) -> Self {
-        let font = Font::from_file(file).expect("failed to parse font");
-        let (editor_offs_x, editor_offs_y) = font.calculate_editor_offset(fb_width, fb_height);
+    pub fn new(
+        fb_width: u32,
+        fb_height: u32,
+        font: Font,
+        output_path: Option<PathBuf>,
+        make_backups: bool,
+        source_options: SourceOptions,
+    ) -> Self {
+        let (editor_offs_x, editor_offs_y) =
+            calculate_editor_offset(font.width, font.height, fb_width, fb_height);
 
         Self {
             message_queue: vec![],
             fb: Framebuffer::new(fb_width, fb_height),
             font,
-            glyph_hov: (0, 0),
-            glyph_sel: (0, 0),
+            glyph_hov: 0,
+            glyph_sel: 0,
             inside_glyphs_area: false,
+            grid_scroll: 0,
             editor_hov: (0, 0),
             inside_editor_area: false,
             editor_offs_x,
             editor_offs_y,
             drawing: false,
             drawing_sets_bits_to: true,
-            saves_counter: 0,
+            stroke_start: None,
+            stroke_last: None,
+            tool: Tool::Pencil,
+            shape_start: None,
+            shift_wraps: false,
+            history: History::default(),
+            unicode_input: None,
+            output_path,
+            path_input: None,
+            new_font_input: None,
+            resize: None,
+            prompt_key: None,
+            make_backups,
+            source_options,
+            status: None,
         }
     }
 
@@ -82,60 +122,135 @@ impl State {
     }
 
     fn render_glyphs_grid(&mut self) {
-        for (grid_y, row) in self.font.glyphs.chunks(16).enumerate() {
-            for (grid_x, glyph) in row.iter().enumerate() {
-                let fw = self.font.width as u32;
-                let fh = self.font.height as u32;
-                let gx = grid_x as u32;
-                let gy = grid_y as u32;
-                let offset_x = GRID_OFFS_X + gx * fw * 2;
-                let offset_y = GRID_OFFS_Y + gy * fh * 2;
-
-                let (hov_x, hov_y) = self.glyph_hov;
-                let (sel_x, sel_y) = self.glyph_sel;
-                let hovered = gx == hov_x && gy == hov_y;
-                let selected = gx == sel_x && gy == sel_y;
-
-                let border_color = if selected {
-                    0x00aa00
-                } else if hovered {
-                    0x990000
-                } else {
-                    0x770000
-                };
+        let layout = self.grid_layout();
+        let first = self.grid_scroll * layout.columns;
+        let fw = self.font.width as u32;
+        let fh = self.font.height as u32;
 
-                self.fb.draw_rect_hollow(offset_x, offset_y, fw + 2, fh + 2, border_color);
-
-                for y in 0..fh {
-                    for x in 0..fw {
-                        let color = {
-                            if glyph.get(x as usize, y as usize) {
-                                0xffffff
-                            } else if selected {
-                                0x848484
-                            } else if hovered {
-                                0x585858
-                            } else {
-                                0x000000
-                            }
-                        };
-
-                        self.fb.draw_pixel(offset_x + x + 1, offset_y + y + 1, color);
-                    }
+        for (idx, glyph) in self.font.glyphs.iter().enumerate().skip(first) {
+            let slot = idx - first;
+
+            if slot / layout.columns >= layout.rows {
+                break;
+            }
+
+            let (offset_x, offset_y) = layout.cell_position(slot);
+
+            if slot.is_multiple_of(layout.columns) {
+                let label_y = offset_y + 1 + fh.saturating_sub(DIGIT_HEIGHT) / 2;
+                self.fb.draw_number(GRID_OFFS_X, label_y, idx, 0x777777);
+            }
+
+            let hovered = idx == self.glyph_hov;
+            let selected = idx == self.glyph_sel;
+
+            let border_color = if selected {
+                0x00aa00
+            } else if hovered {
+                0x990000
+            } else {
+                0x770000
+            };
+
+            self.fb.draw_rect_hollow(offset_x, offset_y, fw + 2, fh + 2, border_color);
+
+            for y in 0..fh {
+                for x in 0..fw {
+                    let color = {
+                        if glyph.get(x as usize, y as usize) {
+                            0xffffff
+                        } else if selected {
+                            0x848484
+                        } else if hovered {
+                            0x585858
+                        } else {
+                            0x000000
+                        }
+                    };
+
+                    self.fb.draw_pixel(offset_x + x + 1, offset_y + y + 1, color);
                 }
             }
         }
+
+        self.render_grid_scrollbar(&layout);
+    }
+
+    fn render_grid_scrollbar(&mut self, layout: &GridLayout) {
+        let total_rows = layout.total_rows(self.font.glyphs.len());
+
+        if total_rows <= layout.rows {
+            return;
+        }
+
+        let x = layout.x + layout.columns as u32 * layout.cell_w + GRID_MARGIN;
+        let height = layout.rows as u32 * layout.cell_h;
+        let thumb_y = layout.y + height * self.grid_scroll as u32 / total_rows as u32;
+        let thumb_height = (height * layout.rows as u32 / total_rows as u32).max(2);
+
+        self.fb.draw_rect(x, layout.y, GRID_SCROLLBAR_WIDTH, height, 0x222222);
+        self.fb.draw_rect(x, thumb_y, GRID_SCROLLBAR_WIDTH, thumb_height, 0x777777);
+    }
+
+    // Columns shrink so the grid stays left of the editor, rows are limited by the window height
+    fn grid_layout(&self) -> GridLayout {
+        let fw = self.font.width as u32;
+        let fh = self.font.height as u32;
+        let cell_w = (fw * 2).max(fw + 3);
+        let cell_h = (fh * 2).max(fh + 3);
+
+        let last_idx = self.font.glyphs.len().saturating_sub(1);
+        let x = GRID_OFFS_X + Framebuffer::number_width(last_idx) + GRID_MARGIN;
+        let right = x + GRID_MARGIN + GRID_SCROLLBAR_WIDTH + GRID_MARGIN;
+        let available_w = self.editor_offs_x.saturating_sub(right);
+        let available_h = self.fb.height.saturating_sub(GRID_OFFS_Y);
+
+        GridLayout {
+            x,
+            y: GRID_OFFS_Y,
+            cell_w,
+            cell_h,
+            columns: (available_w / cell_w).clamp(1, GRID_MAX_COLUMNS) as usize,
+            rows: (available_h / cell_h).max(1) as usize,
+        }
+    }
+
+    fn scroll_grid(&mut self, rows: isize) {
+        let layout = self.grid_layout();
+        let total_rows = layout.total_rows(self.font.glyphs.len());
+        let max_scroll = total_rows.saturating_sub(layout.rows);
+
+        self.grid_scroll = self.grid_scroll.saturating_add_signed(rows).min(max_scroll);
     }
 
     fn render_glyph_editor(&mut self) {
         let sel_glyph = &self.font.glyphs[self.get_selected_index()];
-        let fh = self.font.height as u32;
-        let fw = self.font.width as u32;
+        let resized = self.resize.map(|resize| {
+            sel_glyph.resized(resize.width, resize.height, resize.columns_at, resize.rows_at)
+        });
+        let shape = self.shape_start.filter(|_| resized.is_none()).map(|start| {
+            let mut shape = sel_glyph.clone();
+            let val = self.drawing_sets_bits_to;
+            tools::draw_shape(&mut shape, self.tool, start, self.editor_hov, val);
+            shape
+        });
+        let shown = resized.as_ref().or(shape.as_ref()).unwrap_or(sel_glyph);
+        let (fw, fh) = self.editor_size();
+        let (fw, fh) = (fw as u32, fh as u32);
 
         for cell_y in 0..fh {
             for cell_x in 0..fw {
-                let filled = sel_glyph.get(cell_x as usize, cell_y as usize);
-                let color = if filled { 0xffffff } else { 0x111111 };
+                let (cx, cy) = (cell_x as usize, cell_y as usize);
+                let filled = shown.get(cx, cy);
+
+                // Cells the shape being dragged out would change are tinted
+                let pending = shape.is_some() && filled != sel_glyph.get(cx, cy);
+                let color = match (filled, pending) {
+                    (true, false) => 0xffffff,
+                    (false, false) => 0x111111,
+                    (true, true) => 0x88bbff,
+                    (false, true) => 0x223355,
+                };
 
                 let x = self.editor_offs_x + cell_x * EDITOR_CELL_SIZE;
                 let y = self.editor_offs_y + cell_y * EDITOR_CELL_SIZE;
@@ -145,6 +260,11 @@ impl State {
             }
         }
 
+        if let Some(resize) = self.resize {
+            self.render_resize_outline(resize);
+            return;
+        }
+
         if self.inside_editor_area {
             let (hov_x, hov_y) = self.editor_hov;
             let x = self.editor_offs_x as i32 + hov_x * EDITOR_CELL_SIZE as i32;
@@ -153,45 +273,131 @@ impl State {
         }
     }
 
+    // Marks where the current canvas ends up inside the resized one
+    fn render_resize_outline(&mut self, resize: Resize) {
+        let (old_w, old_h) = (self.font.width as i32, self.font.height as i32);
+        let (new_w, new_h) = (resize.width as i32, resize.height as i32);
+        let dx = resize.columns_at.offset(self.font.width, resize.width);
+        let dy = resize.rows_at.offset(self.font.height, resize.height);
+
+        let (left, right) = (dx.max(0), (dx + old_w).min(new_w));
+        let (top, bottom) = (dy.max(0), (dy + old_h).min(new_h));
+
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        let cell = EDITOR_CELL_SIZE as i32;
+        let x = self.editor_offs_x + (left * cell) as u32;
+        let y = self.editor_offs_y + (top * cell) as u32;
+        let w = ((right - left) * cell) as u32 + 1;
+        let h = ((bottom - top) * cell) as u32 + 1;
+
+        self.fb.draw_rect_hollow(x, y, w, h, 0x0077cc);
+    }
+
     pub fn events(&mut self, event: Event) {
+        // The key that opened a prompt also arrives as text input right after its key press
+        let prompt_key = match event {
+            Event::KeyPress(_) | Event::TextInput(_) => self.prompt_key.take(),
+            _ => self.prompt_key,
+        };
+
+        // Keys can edit or replace the font, so a stroke that is still going on ends first and
+        // lands in the history before whatever the key does
+        if self.drawing && matches!(event, Event::KeyPress(_)) {
+            self.end_drawing();
+        }
+
         match event {
+            Event::KeyPress(key) if self.unicode_input.is_some() => self.unicode_input_key(key),
+            Event::KeyPress(key) if self.path_input.is_some() => self.path_input_key(key),
+            Event::KeyPress(key) if self.new_font_input.is_some() => self.new_font_input_key(key),
+            Event::KeyPress(key) if self.resize.is_some() => self.resize_key(key),
+            Event::TextInput(text) if prompt_key.is_some_and(|key| text == key.to_string()) => (),
+            Event::TextInput(text) => {
+                if let Some(input) = self.path_input.as_mut().or(self.new_font_input.as_mut()) {
+                    input.push_str(&text);
+                    self.update_title();
+                }
+            }
             Event::KeyPress(key) => match key {
                 KeyButton::Escape => self.message_queue.push(Message::Quit),
-                KeyButton::Minus => {
+                // Glyphs can't lose their last row
+                KeyButton::Minus if self.font.height > 1 => {
+                    self.history.record(Change::Font(self.font.clone()));
                     self.font.decrease_height();
-                    let fb_w = self.fb.width;
-                    let fb_h = self.fb.height;
-                    let (offs_x, offs_y) = self.font.calculate_editor_offset(fb_w, fb_h);
-                    self.editor_offs_x = offs_x;
-                    self.editor_offs_y = offs_y;
+                    self.update_editor_offset();
                 }
+                KeyButton::Minus => (),
+                KeyButton::PageUp => self.scroll_grid(-(self.grid_layout().rows as isize)),
+                KeyButton::PageDown => self.scroll_grid(self.grid_layout().rows as isize),
+                KeyButton::Character('z') => self.undo(),
+                KeyButton::Character('y') => self.redo(),
                 KeyButton::Character('w') => self.save_file(),
+                KeyButton::Character('a') => {
+                    self.prompt_key = Some('a');
+                    self.start_save_as();
+                }
+                KeyButton::Character('v') => self.toggle_version(),
+                KeyButton::Character('u') => {
+                    self.unicode_input = Some(String::new());
+                    self.update_title();
+                }
                 KeyButton::Character('c') => self.clear_extend_ascii(),
+                KeyButton::Character('n') => {
+                    let blank = BlankFont::default();
+                    let (w, h, count) = (blank.width, blank.height, blank.glyphs);
+                    self.new_font_input = Some(format!("{w}x{h}x{count}"));
+                    self.prompt_key = Some('n');
+                    self.update_title();
+                }
+                KeyButton::Character('m') => self.transform_glyph(BitMatrix::flip_horizontal),
+                KeyButton::Character('f') => self.transform_glyph(BitMatrix::flip_vertical),
+                KeyButton::Character('i') => self.transform_glyph(BitMatrix::invert),
+                KeyButton::Character('t') => self.rotate_glyph(),
+                KeyButton::Up => self.shift_glyph(0, -1),
+                KeyButton::Down => self.shift_glyph(0, 1),
+                KeyButton::Left => self.shift_glyph(-1, 0),
+                KeyButton::Right => self.shift_glyph(1, 0),
+                KeyButton::Character('e') => {
+                    self.shift_wraps = !self.shift_wraps;
+                    self.status = Some(if self.shift_wraps {
+                        "shifting wraps around the edges".to_string()
+                    } else {
+                        "shifting drops pixels at the edges".to_string()
+                    });
+                    self.update_title();
+                }
+                KeyButton::Character(key) if key.is_ascii_digit() => self.select_tool(key),
+                KeyButton::Character('r') => {
+                    self.resize = Some(Resize {
+                        width: self.font.width,
+                        height: self.font.height,
+                        columns_at: Edge::End,
+                        rows_at: Edge::End,
+                    });
+                    self.update_title();
+                }
                 _ => (),
             },
             Event::MouseMotion(x, y) => {
                 self.detect_mouse_hover(x, y);
 
-                if self.drawing {
-                    let (hov_x, hov_y) = self.editor_hov;
-                    let sel_idx = self.get_selected_index();
-                    let sel_glyph = &mut self.font.glyphs[sel_idx];
-
-                    sel_glyph.set_to(hov_x as usize, hov_y as usize, self.drawing_sets_bits_to);
+                if self.drawing && self.tool == Tool::Pencil {
+                    self.continue_stroke(self.editor_cell(x, y));
                 }
             }
             Event::MousePress(button, x, y) => {
                 self.detect_mouse_hover(x, y);
 
                 if self.inside_glyphs_area {
+                    self.end_drawing();
                     self.glyph_sel = self.glyph_hov;
-
-                    let title = format!("psfe | index = {}", self.get_selected_index());
-                    self.message_queue.push(Message::ChangeWindowTitle(title));
+                    self.update_title();
 
                     if button == MouseButton::Right {
-                        let sel_idx = self.get_selected_index();
-                        self.font.glyphs[sel_idx].clear_all();
+                        self.transform_glyph(BitMatrix::clear_all);
                     }
 
                     return;
@@ -207,14 +413,24 @@ impl State {
                     if !self.drawing {
                         self.drawing = true;
                         self.drawing_sets_bits_to = button == MouseButton::Left;
+                        self.stroke_start = Some((sel_idx, sel_glyph.clone()));
+
+                        if self.tool.is_shape() {
+                            self.shape_start = Some(self.editor_hov);
+                        }
                     }
 
-                    sel_glyph.set_to(hov_x, hov_y, self.drawing_sets_bits_to);
+                    let val = self.drawing_sets_bits_to;
+
+                    match self.tool {
+                        Tool::Pencil => self.continue_stroke(self.editor_hov),
+                        Tool::Fill => tools::flood_fill(sel_glyph, (hov_x, hov_y), val),
+                        _ => (),
+                    }
                 }
             }
-            Event::MouseRelease(_, _) => {
-                self.drawing = false;
-            }
+            Event::MouseRelease(_, _) => self.end_drawing(),
+            Event::MouseWheel(y) => self.scroll_grid(-y as isize),
             _ => (),
         }
     }
@@ -223,36 +439,28 @@ impl State {
         let fw = self.font.width as i32;
         let fh = self.font.height as i32;
 
-        let mut gx = x;
-        let mut gy = y;
+        let layout = self.grid_layout();
+        let gx = x - layout.x as i32;
+        let gy = y - layout.y as i32;
 
-        gx -= GRID_OFFS_X as i32;
-        gy -= GRID_OFFS_Y as i32;
+        if gx >= 0 && gy >= 0 {
+            let column = (gx as u32 / layout.cell_w) as usize;
+            let row = (gy as u32 / layout.cell_h) as usize;
+            let idx = (self.grid_scroll + row) * layout.columns + column;
 
-        gx /= 2;
-        gy /= 2;
-
-        gx /= fw;
-        gy /= fh;
-
-        if gx < 16 && gy < 16 {
-            self.glyph_hov = (gx as u32, gy as u32);
-            self.inside_glyphs_area = true;
-            return;
+            if column < layout.columns && row < layout.rows && idx < self.font.glyphs.len() {
+                self.glyph_hov = idx;
+                self.inside_glyphs_area = true;
+                return;
+            }
         }
 
         self.inside_glyphs_area = false;
 
-        let mut cx = x;
-        let mut cy = y;
-
-        cx -= self.editor_offs_x as i32;
-        cy -= self.editor_offs_y as i32;
-
-        cx /= EDITOR_CELL_SIZE as i32;
-        cy /= EDITOR_CELL_SIZE as i32;
+        let (cx, cy) = self.editor_cell(x, y);
 
-        if cx >= 0 && cx < fw && cy >= 0 && cy < fh {
+        // The preview can't be drawn on
+        if self.resize.is_none() && cx >= 0 && cx < fw && cy >= 0 && cy < fh {
             self.editor_hov = (cx, cy);
             self.inside_editor_area = true;
             return;
@@ -261,174 +469,492 @@ impl State {
         self.inside_editor_area = false;
     }
 
-    fn get_selected_index(&self) -> usize {
-        let (sel_x, sel_y) = self.glyph_sel;
-        let sel_idx = sel_y * 16 + sel_x;
-        sel_idx as usize
-    }
+    // The cell under a window position, which may be outside the glyph
+    fn editor_cell(&self, x: i32, y: i32) -> (i32, i32) {
+        let cell_size = EDITOR_CELL_SIZE as i32;
+        let cx = (x - self.editor_offs_x as i32).div_euclid(cell_size);
+        let cy = (y - self.editor_offs_y as i32).div_euclid(cell_size);
 
-    fn save_file(&mut self) {
-        let filename = format!("saved_font{:03}.psf", self.saves_counter);
-        self.saves_counter += 1;
+        (cx, cy)
+    }
 
-        let file = self.construct_font_file();
+    fn undo(&mut self) {
+        if self.history.undo(&mut self.font) {
+            self.font_replaced();
+        }
+    }
 
-        match std::fs::write(&filename, file) {
-            Ok(_) => println!("saved to file \"{filename}\""),
-            Err(_) => println!("failed to write to file \"{filename}\""),
+    fn redo(&mut self) {
+        if self.history.redo(&mut self.font) {
+            self.font_replaced();
         }
     }
 
-    fn construct_font_file(&self) -> Vec<u8> {
-        let mut file = vec![
-            PSF1_MAGIC0,
-            PSF1_MAGIC1,
-            0,                // mode
-            self.font.height, // charsize
-        ];
+    // The glyph count and size may have changed, so the selection, scroll position and
+    // editor placement are brought back in range
+    fn font_replaced(&mut self) {
+        let last_idx = self.font.glyphs.len().saturating_sub(1);
 
-        for glyph in &self.font.glyphs {
-            for row in glyph.serialize() {
-                file.push(row);
-            }
+        self.glyph_sel = self.glyph_sel.min(last_idx);
+        self.glyph_hov = self.glyph_hov.min(last_idx);
+        self.scroll_grid(0);
+        self.update_editor_offset();
+        self.update_title();
+    }
+
+    fn transform_glyph(&mut self, transform: impl FnOnce(&mut BitMatrix)) {
+        let sel_idx = self.get_selected_index();
+        let glyph = self.font.glyphs[sel_idx].clone();
+
+        transform(&mut self.font.glyphs[sel_idx]);
+
+        if self.font.glyphs[sel_idx] != glyph {
+            self.history.record(Change::Glyph(sel_idx, glyph));
+        }
+    }
+
+    fn rotate_glyph(&mut self) {
+        if self.font.width != self.font.height {
+            self.status = Some("only square glyphs can be rotated".to_string());
+            self.update_title();
+            return;
         }
 
-        file
+        self.transform_glyph(BitMatrix::rotate);
     }
 
-    fn clear_extend_ascii(&mut self) {
-        for i in 128..256 {
-            self.font.glyphs[i].clear_all();
+    fn shift_glyph(&mut self, dx: i32, dy: i32) {
+        let wrap = self.shift_wraps;
+        self.transform_glyph(|glyph| glyph.shift(dx, dy, wrap));
+    }
+
+    fn select_tool(&mut self, key: char) {
+        if let Some(tool) = Tool::from_key(key) {
+            self.tool = tool;
+            self.update_title();
         }
     }
-}
 
-impl Font {
-    fn from_file(file: &[u8]) -> Option<Self> {
-        if file[0..2] == [PSF1_MAGIC0, PSF1_MAGIC1] {
-            return Some(Self::parse_psf1(file));
+    // Shapes are only previewed while they're dragged out and land in the glyph on release
+    fn finish_shape(&mut self) {
+        let (Some(start), Some((idx, _))) = (self.shape_start.take(), &self.stroke_start) else {
+            return;
+        };
+
+        let glyph = &mut self.font.glyphs[*idx];
+        tools::draw_shape(glyph, self.tool, start, self.editor_hov, self.drawing_sets_bits_to);
+    }
+
+    // Motion events can be several cells apart, so the stroke is drawn as a line from the
+    // cell it was last at. That cell may be outside the glyph if the cursor left the editor
+    fn continue_stroke(&mut self, cell: (i32, i32)) {
+        let Some((idx, _)) = &self.stroke_start else {
+            return;
+        };
+
+        let (fw, fh) = (self.font.width as i32, self.font.height as i32);
+        let glyph = &mut self.font.glyphs[*idx];
+
+        for (x, y) in tools::line(self.stroke_last.unwrap_or(cell), cell) {
+            if (0..fw).contains(&x) && (0..fh).contains(&y) {
+                glyph.set_to(x as usize, y as usize, self.drawing_sets_bits_to);
+            }
         }
 
-        None
+        self.stroke_last = Some(cell);
+    }
+
+    fn end_drawing(&mut self) {
+        self.drawing = false;
+        self.finish_shape();
+        self.end_stroke();
     }
 
-    fn parse_psf1(file: &[u8]) -> Self {
-        let height = file[3];
+    fn end_stroke(&mut self) {
+        self.stroke_last = None;
 
-        Self {
-            _version: 1,
-            width: 8,
-            height,
-            glyphs: Self::parse_psf1_glyphs(height, file),
+        let Some((idx, glyph)) = self.stroke_start.take() else {
+            return;
+        };
+
+        if self.font.glyphs[idx] != glyph {
+            self.history.record(Change::Glyph(idx, glyph));
         }
     }
 
-    fn parse_psf1_glyphs(height: u8, file: &[u8]) -> Vec<BitMatrix> {
-        let mode = file[2];
-        let num_glyphs = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
-        let mut glyphs = vec![];
-        let glyph_data = &file[4..];
+    fn update_editor_offset(&mut self) {
+        let (fw, fh) = self.editor_size();
+        let (offs_x, offs_y) = calculate_editor_offset(fw, fh, self.fb.width, self.fb.height);
+        self.editor_offs_x = offs_x;
+        self.editor_offs_y = offs_y;
+    }
 
-        for i in 0..num_glyphs {
-            let mut glyph = BitMatrix::new(8, height);
-            let offset = i * height as usize;
+    // The editor shows the pending size while a resize is previewed
+    fn editor_size(&self) -> (u8, u8) {
+        match self.resize {
+            Some(resize) => (resize.width, resize.height),
+            None => (self.font.width, self.font.height),
+        }
+    }
 
-            for h in 0..height {
-                let row = glyph_data[offset + h as usize];
+    fn get_selected_index(&self) -> usize {
+        self.glyph_sel
+    }
 
-                for bit in 0..8 {
-                    let mask = 1 << bit;
+    fn update_title(&mut self) {
+        let sel_idx = self.get_selected_index();
+        let version = self.font.save_version();
+        let mut title = format!("psfe | PSF{version} | index = {sel_idx}");
 
-                    if row & mask != 0 {
-                        glyph.set(8 - bit - 1, h.into());
-                    }
-                }
+        if let Some(entry) = self.font.unicode_entry(sel_idx) {
+            for codepoint in &entry.codepoints {
+                title += &format!(" U+{codepoint:04X}");
             }
 
-            glyphs.push(glyph);
+            for sequence in &entry.sequences {
+                let codepoints: Vec<String> =
+                    sequence.iter().map(|c| format!("U+{c:04X}")).collect();
+                title += &format!(" [{}]", codepoints.join(" "));
+            }
+        }
+
+        if self.tool != Tool::Pencil {
+            title += &format!(" | {}", self.tool.name());
         }
 
-        glyphs
+        if let Some(input) = &self.unicode_input {
+            title += &format!(" | U+{input}_");
+        }
+
+        if let Some(input) = &self.path_input {
+            title += &format!(" | save as: {input}_");
+        } else if let Some(input) = &self.new_font_input {
+            title += &format!(" | new font WxH[xN][u]: {input}_");
+        } else if let Some(resize) = self.resize {
+            let rows_at = match resize.rows_at {
+                Edge::Start => "top",
+                Edge::Center => "top and bottom",
+                Edge::End => "bottom",
+            };
+            let columns_at = match resize.columns_at {
+                Edge::Start => "left",
+                Edge::Center => "left and right",
+                Edge::End => "right",
+            };
+
+            title += &format!(
+                " | resize {}x{} to {}x{}, rows at {rows_at}, columns at {columns_at} \
+                 (arrows, t/m/b, l/c/r)",
+                self.font.width, self.font.height, resize.width, resize.height
+            );
+        } else if let Some(status) = &self.status {
+            title += &format!(" | {status}");
+        }
+
+        self.message_queue.push(Message::ChangeWindowTitle(title));
     }
 
-    fn calculate_editor_offset(&self, fb_width: u32, fb_height: u32) -> (u32, u32) {
-        let fw = self.width as u32;
-        let fh = self.height as u32;
-        let x = fb_width / 2 - fw * EDITOR_CELL_SIZE / 2;
-        let y = fb_height / 2 - fh * EDITOR_CELL_SIZE / 2;
+    fn unicode_input_key(&mut self, key: KeyButton) {
+        let sel_idx = self.get_selected_index();
+        let Some(input) = &mut self.unicode_input else {
+            return;
+        };
+
+        match key {
+            KeyButton::Escape => self.unicode_input = None,
+            KeyButton::Character(c) if c.is_ascii_hexdigit() && input.len() < 6 => input.push(c),
+            KeyButton::Return => {
+                let codepoint = u32::from_str_radix(input, 16).ok();
+
+                if let Some(codepoint) = codepoint.filter(|&c| c <= 0x10ffff) {
+                    let entry = self.font.unicode_entry(sel_idx).cloned();
+                    self.history.record(Change::Unicode(sel_idx, entry));
+                    self.font.unicode_entry_mut(sel_idx).codepoints.push(codepoint);
+                }
+
+                input.clear();
+            }
+            KeyButton::Backspace if input.is_empty() => {
+                let entry = self.font.unicode_entry(sel_idx).cloned();
+                self.history.record(Change::Unicode(sel_idx, entry));
+                self.font.unicode_entry_mut(sel_idx).codepoints.pop();
+            }
+            KeyButton::Backspace => {
+                input.pop();
+            }
+            _ => (),
+        }
 
-        (x, y)
+        self.update_title();
     }
 
-    fn decrease_height(&mut self) {
-        self.height -= 1;
+    fn path_input_key(&mut self, key: KeyButton) {
+        let Some(input) = &mut self.path_input else {
+            return;
+        };
 
-        for glyph in &mut self.glyphs {
-            glyph.decrease_height();
+        match key {
+            KeyButton::Escape => self.path_input = None,
+            KeyButton::Backspace => {
+                input.pop();
+            }
+            KeyButton::Return if !input.is_empty() => {
+                let path = PathBuf::from(std::mem::take(input));
+                self.path_input = None;
+                self.save_file_as(path);
+            }
+            _ => (),
         }
+
+        self.update_title();
     }
-}
 
-impl BitMatrix {
-    fn new(width: u8, height: u8) -> Self {
-        let mut data = Vec::new();
-        let w: usize = width.into();
-        let h: usize = height.into();
+    fn new_font_input_key(&mut self, key: KeyButton) {
+        let Some(input) = &mut self.new_font_input else {
+            return;
+        };
 
-        data.resize(w * h, false);
+        match key {
+            KeyButton::Escape => self.new_font_input = None,
+            KeyButton::Backspace => {
+                input.pop();
+            }
+            KeyButton::Return => {
+                match BlankFont::parse(input) {
+                    Some(blank) => self.new_font(&blank),
+                    None => self.status = Some(format!("invalid font size \"{input}\"")),
+                }
 
-        Self {
-            width,
-            height,
-            data,
+                self.new_font_input = None;
+            }
+            _ => (),
         }
+
+        self.update_title();
     }
 
-    fn set_to(&mut self, x: usize, y: usize, val: bool) {
-        assert!(x < self.width.into());
-        assert!(y < self.height.into());
+    fn resize_key(&mut self, key: KeyButton) {
+        let Some(resize) = &mut self.resize else {
+            return;
+        };
+
+        match key {
+            KeyButton::Escape => self.resize = None,
+            KeyButton::Up => resize.height = resize.height.saturating_sub(1).max(1),
+            KeyButton::Down => resize.height = resize.height.saturating_add(1),
+            KeyButton::Left => resize.width = resize.width.saturating_sub(1).max(1),
+            KeyButton::Right => resize.width = resize.width.saturating_add(1),
+            KeyButton::Character('t') => resize.rows_at = Edge::Start,
+            KeyButton::Character('m') => resize.rows_at = Edge::Center,
+            KeyButton::Character('b') => resize.rows_at = Edge::End,
+            KeyButton::Character('l') => resize.columns_at = Edge::Start,
+            KeyButton::Character('c') => resize.columns_at = Edge::Center,
+            KeyButton::Character('r') => resize.columns_at = Edge::End,
+            KeyButton::Return => {
+                let resize = *resize;
+                self.resize = None;
+                self.resize_font(resize);
+            }
+            _ => (),
+        }
+
+        self.update_editor_offset();
+        self.update_title();
+    }
 
-        let w: usize = self.width.into();
+    fn resize_font(&mut self, resize: Resize) {
+        if (resize.width, resize.height) == (self.font.width, self.font.height) {
+            return;
+        }
 
-        self.data[y * w + x] = val;
+        self.history.record(Change::Font(self.font.clone()));
+        self.font.resize(resize.width, resize.height, resize.columns_at, resize.rows_at);
+        self.font_replaced();
     }
 
-    fn set(&mut self, x: usize, y: usize) {
-        self.set_to(x, y, true);
+    // The previous font stays reachable through undo, but is no longer the save target
+    fn new_font(&mut self, blank: &BlankFont) {
+        self.history.record(Change::Font(self.font.clone()));
+        self.font = blank.build();
+        self.output_path = None;
+        self.status = None;
+        self.glyph_sel = 0;
+        self.grid_scroll = 0;
+        self.font_replaced();
     }
 
-    fn get(&self, x: usize, y: usize) -> bool {
-        assert!(x < self.width.into());
-        assert!(y < self.height.into());
+    fn start_save_as(&mut self) {
+        let path = self.output_path.as_ref().map(|path| path.display().to_string());
 
-        let w: usize = self.width.into();
+        self.path_input = Some(path.unwrap_or_default());
+        self.update_title();
+    }
 
-        self.data[y * w + x]
+    fn save_file(&mut self) {
+        match self.output_path.clone() {
+            Some(path) => self.save_file_as(path),
+            None => {
+                self.prompt_key = Some('w');
+                self.start_save_as();
+            }
+        }
     }
 
-    fn clear_all(&mut self) {
-        self.data.fill(false);
+    fn save_file_as(&mut self, path: PathBuf) {
+        let written =
+            formats::encode_font(&self.font, &path, &self.source_options).and_then(|file| {
+                save::write_atomic(&path, &file, self.make_backups).map_err(|err| err.to_string())
+            });
+
+        let status = match written {
+            Ok(()) => {
+                let status = format!("saved to \"{}\"", path.display());
+                self.output_path = Some(path);
+                status
+            }
+            Err(err) => format!("failed to save to \"{}\": {err}", path.display()),
+        };
+
+        self.status = Some(status);
+        self.update_title();
     }
 
-    fn serialize(&self) -> Vec<u8> {
-        let mut rows = vec![];
+    fn toggle_version(&mut self) {
+        let version = if self.font.version == 1 { 2 } else { 1 };
 
-        for y in 0..self.height {
-            let mut row = 0;
+        if version == 1 && !self.font.fits_psf1() {
+            return;
+        }
 
-            for x in 0..self.width {
-                let bit = self.get((8 - x - 1) as usize, y as usize);
-                row |= u8::from(bit) << x;
-            }
+        self.font.version = version;
+        self.update_title();
+    }
 
-            rows.push(row);
+    fn clear_extend_ascii(&mut self) {
+        let num_glyphs = self.font.glyphs.len();
+        let range = num_glyphs.min(128)..num_glyphs.min(256);
+        let glyphs = self.font.glyphs[range.clone()].to_vec();
+
+        for glyph in &mut self.font.glyphs[range.clone()] {
+            glyph.clear_all();
+        }
+
+        if self.font.glyphs[range.clone()] != glyphs {
+            self.history.record(Change::Glyphs(range.start, glyphs));
         }
+    }
+}
+
+fn calculate_editor_offset(width: u8, height: u8, fb_width: u32, fb_height: u32) -> (u32, u32) {
+    let fw = width as u32;
+    let fh = height as u32;
+    let x = (fb_width / 2).saturating_sub(fw * EDITOR_CELL_SIZE / 2);
+    let y = (fb_height / 2).saturating_sub(fh * EDITOR_CELL_SIZE / 2);
+
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(font: Font) -> State {
+        State::new(640, 480, font, None, false, SourceOptions::default())
+    }
+
+    fn click_glyph(state: &mut State, idx: usize, button: MouseButton) {
+        let layout = state.grid_layout();
+        let slot = idx - state.grid_scroll * layout.columns;
+        let (x, y) = layout.cell_position(slot);
+
+        state.events(Event::MousePress(button, x as i32 + 1, y as i32 + 1));
+        state.events(Event::MouseRelease(0, 0));
+    }
+
+    #[test]
+    fn grid_reaches_every_glyph() {
+        let mut state = state(Font::new(8, 16, 500));
+        let layout = state.grid_layout();
+        let total_rows = layout.total_rows(500);
+        assert!(layout.rows < total_rows);
+
+        // Scrolling stops once the last row is visible
+        state.events(Event::MouseWheel(-1000));
+        assert_eq!(state.grid_scroll, total_rows - layout.rows);
+
+        click_glyph(&mut state, 499, MouseButton::Left);
+        assert_eq!(state.glyph_sel, 499);
+
+        // Cells past the last glyph select nothing
+        assert_ne!(500 % layout.columns, 0);
+        let (x, y) = layout.cell_position(layout.rows * layout.columns - 1);
+        state.events(Event::MousePress(MouseButton::Left, x as i32 + 1, y as i32 + 1));
+        state.events(Event::MouseRelease(0, 0));
+        assert_eq!(state.glyph_sel, 499);
+
+        state.events(Event::KeyPress(KeyButton::PageUp));
+        assert_eq!(state.grid_scroll, total_rows.saturating_sub(2 * layout.rows));
+
+        let first = state.grid_scroll * layout.columns;
+        click_glyph(&mut state, first, MouseButton::Left);
+        assert_eq!(state.glyph_sel, first);
+
+        // Glyphs wider than the space left of the editor still get a column
+        let state = self::state(Font::new(255, 8, 4));
+        assert_eq!(state.grid_layout().columns, 1);
+    }
 
-        rows
+    #[test]
+    fn strokes_are_continuous() {
+        let mut state = state(Font::new(8, 8, 256));
+        let (x, y) = (state.editor_offs_x as i32, state.editor_offs_y as i32);
+        let cell = EDITOR_CELL_SIZE as i32;
+        let at = |cx: i32, cy: i32| (x + cx * cell + cell / 2, y + cy * cell + cell / 2);
+        let set_cells = |state: &State| {
+            let glyph = &state.font.glyphs[0];
+            let cells = (0..8).flat_map(|cy| (0..8).map(move |cx| (cx, cy)));
+            cells.filter(|&(cx, cy)| glyph.get(cx, cy)).collect::<Vec<_>>()
+        };
+
+        // One motion event across the whole glyph still draws the diagonal
+        let (px, py) = at(0, 0);
+        state.events(Event::MousePress(MouseButton::Left, px, py));
+        let (mx, my) = at(7, 7);
+        state.events(Event::MouseMotion(mx, my));
+
+        // Leaving the editor and coming back keeps painting, from where the cursor returns
+        let (mx, my) = at(12, 3);
+        state.events(Event::MouseMotion(mx, my));
+        let (mx, my) = at(4, 3);
+        state.events(Event::MouseMotion(mx, my));
+        state.events(Event::MouseRelease(mx, my));
+
+        let diagonal = (0..8).map(|c| (c, c));
+        let mut expected: Vec<_> = diagonal.chain([(4, 3), (5, 3), (6, 3), (7, 3)]).collect();
+        expected.sort_by_key(|&(cx, cy)| (cy, cx));
+        assert_eq!(set_cells(&state), expected);
+
+        // The whole stroke is a single undo step
+        state.events(Event::KeyPress(KeyButton::Character('z')));
+        assert!(set_cells(&state).is_empty());
+        assert!(!state.history.undo(&mut state.font));
     }
 
-    fn decrease_height(&mut self) {
-        self.height -= 1;
-        self.data = self.data.split_off(self.width.into());
+    #[test]
+    fn clearing_nothing_is_not_recorded() {
+        let mut state = state(Font::new(8, 8, 256));
+        state.font.glyphs[2].set(1, 1);
+
+        click_glyph(&mut state, 1, MouseButton::Right);
+        click_glyph(&mut state, 2, MouseButton::Right);
+        assert!(!state.font.glyphs[2].get(1, 1));
+        assert!(state.history.undo(&mut state.font));
+        assert!(state.font.glyphs[2].get(1, 1));
+        assert!(!state.history.undo(&mut state.font));
+
+        // Fonts of up to 128 glyphs have no extended ASCII to clear
+        let mut state = self::state(Font::new(8, 8, 128));
+        state.events(Event::KeyPress(KeyButton::Character('c')));
+        assert!(!state.history.undo(&mut state.font));
     }
 }
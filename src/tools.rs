@@ -0,0 +1,211 @@
+use psfe::font::BitMatrix;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tool {
+    Pencil,
+    Line,
+    Rect,
+    FilledRect,
+    Ellipse,
+    FilledEllipse,
+    Fill,
+}
+
+impl Tool {
+    pub fn from_key(key: char) -> Option<Self> {
+        match key {
+            '1' => Some(Tool::Pencil),
+            '2' => Some(Tool::Line),
+            '3' => Some(Tool::Rect),
+            '4' => Some(Tool::FilledRect),
+            '5' => Some(Tool::Ellipse),
+            '6' => Some(Tool::FilledEllipse),
+            '7' => Some(Tool::Fill),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Pencil => "pencil",
+            Tool::Line => "line",
+            Tool::Rect => "rectangle",
+            Tool::FilledRect => "filled rectangle",
+            Tool::Ellipse => "ellipse",
+            Tool::FilledEllipse => "filled ellipse",
+            Tool::Fill => "flood fill",
+        }
+    }
+
+    // Shapes are dragged out from one cell to another, the other tools act where they're used
+    pub fn is_shape(self) -> bool {
+        !matches!(self, Tool::Pencil | Tool::Fill)
+    }
+}
+
+// Both ends must be inside the glyph, rectangles and ellipses fit the box they span
+pub fn draw_shape(glyph: &mut BitMatrix, tool: Tool, from: (i32, i32), to: (i32, i32), val: bool) {
+    let cells = match tool {
+        Tool::Line => line(from, to),
+        Tool::Rect => rect(from, to, false),
+        Tool::FilledRect => rect(from, to, true),
+        Tool::Ellipse => ellipse(from, to, false),
+        Tool::FilledEllipse => ellipse(from, to, true),
+        Tool::Pencil | Tool::Fill => vec![],
+    };
+
+    for (x, y) in cells {
+        glyph.set_to(x as usize, y as usize, val);
+    }
+}
+
+// Bresenham's algorithm, both ends included
+pub fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut cells = vec![(x, y)];
+
+    while (x, y) != to {
+        let err2 = 2 * err;
+
+        if err2 >= dy {
+            err += dy;
+            x += step_x;
+        }
+
+        if err2 <= dx {
+            err += dx;
+            y += step_y;
+        }
+
+        cells.push((x, y));
+    }
+
+    cells
+}
+
+fn rect(from: (i32, i32), to: (i32, i32), filled: bool) -> Vec<(i32, i32)> {
+    let (left, right) = (from.0.min(to.0), from.0.max(to.0));
+    let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+
+    (top..=bottom)
+        .flat_map(|y| (left..=right).map(move |x| (x, y)))
+        .filter(|&(x, y)| filled || x == left || x == right || y == top || y == bottom)
+        .collect()
+}
+
+// Cells whose centres fall inside the ellipse, the outline keeps the ones next to a cell
+// outside of it
+fn ellipse(from: (i32, i32), to: (i32, i32), filled: bool) -> Vec<(i32, i32)> {
+    let (left, right) = (from.0.min(to.0), from.0.max(to.0));
+    let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+
+    let center_x = f64::from(left + right) / 2.0;
+    let center_y = f64::from(top + bottom) / 2.0;
+
+    // Stretching the radii a little gives the flat sides more than a single cell without
+    // filling the corners of small boxes
+    let radius_x = f64::from(right - left) / 2.0 + 0.4;
+    let radius_y = f64::from(bottom - top) / 2.0 + 0.4;
+
+    let inside = |x: i32, y: i32| {
+        let nx = (f64::from(x) - center_x) / radius_x;
+        let ny = (f64::from(y) - center_y) / radius_y;
+
+        nx * nx + ny * ny <= 1.0
+    };
+    let on_outline = |x: i32, y: i32| {
+        [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter().any(|&(x, y)| !inside(x, y))
+    };
+
+    rect(from, to, true)
+        .into_iter()
+        .filter(|&(x, y)| inside(x, y) && (filled || on_outline(x, y)))
+        .collect()
+}
+
+// Four-way fill of the area that has the same value as the starting cell
+pub fn flood_fill(glyph: &mut BitMatrix, start: (usize, usize), val: bool) {
+    let (w, h) = (glyph.width() as usize, glyph.height() as usize);
+    let target = glyph.get(start.0, start.1);
+
+    if target == val {
+        return;
+    }
+
+    let mut stack = vec![start];
+
+    while let Some((x, y)) = stack.pop() {
+        if glyph.get(x, y) != target {
+            continue;
+        }
+
+        glyph.set_to(x, y, val);
+
+        if x > 0 {
+            stack.push((x - 1, y));
+        }
+
+        if x + 1 < w {
+            stack.push((x + 1, y));
+        }
+
+        if y > 0 {
+            stack.push((x, y - 1));
+        }
+
+        if y + 1 < h {
+            stack.push((x, y + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(glyph: &BitMatrix) -> Vec<String> {
+        (0..glyph.height() as usize)
+            .map(|y| {
+                let row = (0..glyph.width() as usize).map(|x| glyph.get(x, y));
+                row.map(|set| if set { '#' } else { '.' }).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shapes() {
+        assert_eq!(line((0, 0), (4, 2)), [(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+        assert_eq!(line((2, 3), (2, 1)), [(2, 3), (2, 2), (2, 1)]);
+
+        let mut glyph = BitMatrix::new(5, 5);
+        draw_shape(&mut glyph, Tool::Rect, (3, 2), (0, 0), true);
+        assert_eq!(rows(&glyph), ["####.", "#..#.", "####.", ".....", "....."]);
+
+        let mut glyph = BitMatrix::new(5, 5);
+        draw_shape(&mut glyph, Tool::Ellipse, (0, 0), (4, 4), true);
+        assert_eq!(rows(&glyph), [".###.", "#...#", "#...#", "#...#", ".###."]);
+
+        draw_shape(&mut glyph, Tool::FilledEllipse, (1, 1), (3, 3), true);
+        assert_eq!(rows(&glyph), [".###.", "#.#.#", "#####", "#.#.#", ".###."]);
+    }
+
+    #[test]
+    fn fill() {
+        let mut glyph = BitMatrix::new(5, 4);
+        draw_shape(&mut glyph, Tool::Rect, (0, 0), (3, 3), true);
+
+        flood_fill(&mut glyph, (1, 1), true);
+        assert_eq!(rows(&glyph), ["####.", "####.", "####.", "####."]);
+
+        flood_fill(&mut glyph, (4, 0), true);
+        assert!(rows(&glyph).iter().all(|row| row == "#####"));
+
+        flood_fill(&mut glyph, (2, 2), false);
+        assert!(rows(&glyph).iter().all(|row| row == "....."));
+    }
+}
@@ -0,0 +1,66 @@
+use psfe::font::{Font, ParseError};
+
+fn parse(name: &str) -> Result<Font, ParseError> {
+    let path = format!("{}/tests/malformed/{name}", env!("CARGO_MANIFEST_DIR"));
+    let file = std::fs::read(&path).expect("failed to read test file");
+
+    Font::from_file(&file)
+}
+
+fn assert_error(name: &str, expected: ParseError) {
+    match parse(name) {
+        Ok(_) => panic!("{name}: parsed successfully, expected {expected:?}"),
+        Err(err) => assert_eq!(err, expected, "{name}"),
+    }
+}
+
+#[test]
+fn bad_magic() {
+    assert_error("empty.psf", ParseError::BadMagic);
+    assert_error("bad_magic.psf", ParseError::BadMagic);
+}
+
+#[test]
+fn truncated_header() {
+    let truncated = |offset, expected| ParseError::TruncatedHeader { offset, expected };
+
+    assert_error("psf1_truncated_header.psf", truncated(3, 4));
+    assert_error("psf2_truncated_header.psf", truncated(20, 32));
+    assert_error("psf2_small_headersize.psf", truncated(16, 32));
+    assert_error("psf2_headersize_past_end.psf", truncated(48, 4096));
+}
+
+#[test]
+fn truncated_glyph_data() {
+    let truncated = |glyph, offset| ParseError::TruncatedGlyphData { glyph, offset };
+
+    assert_error("psf1_truncated_glyphs.psf", truncated(100, 1604));
+    assert_error("psf1_mode512_truncated_glyphs.psf", truncated(256, 2052));
+    assert_error("psf2_truncated_glyphs.psf", truncated(255, 4112));
+}
+
+#[test]
+fn bad_unicode_table() {
+    let bad_table = |glyph, offset| ParseError::BadUnicodeTable { glyph, offset };
+
+    assert_error("psf1_truncated_table.psf", bad_table(200, 2852));
+    assert_error("psf1_odd_table.psf", bad_table(255, 3073));
+    assert_error("psf2_bad_utf8.psf", bad_table(1, 66));
+    assert_error("psf2_overlong_utf8.psf", bad_table(0, 48));
+    assert_error("psf2_truncated_table.psf", bad_table(2, 84));
+}
+
+#[test]
+fn unsupported_dimensions() {
+    let unsupported = |width, height, glyphs| ParseError::UnsupportedDimensions {
+        width,
+        height,
+        glyphs,
+    };
+
+    assert_error("psf1_zero_height.psf", unsupported(8, 0, 256));
+    assert_error("psf2_zero_width.psf", unsupported(0, 16, 256));
+    assert_error("psf2_too_wide.psf", unsupported(300, 16, 1));
+    assert_error("psf2_no_glyphs.psf", unsupported(8, 16, 0));
+    assert_error("psf2_small_charsize.psf", unsupported(12, 16, 1));
+}